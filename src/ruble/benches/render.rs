@@ -0,0 +1,67 @@
+//! Rendering benchmarks: `cargo bench --bench render`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use ruble::{Address, InvoiceData, InvoiceDataBuilder, InvoiceLine, create_invoice_pdf_bytes};
+
+/// A typical small invoice: two parties, a handful of lines and VAT.
+fn sample_invoice(number: usize) -> InvoiceData {
+    let address = |street: &str, city: &str| Address {
+        street: street.to_string(),
+        postal: "10115".to_string(),
+        city: city.to_string(),
+        country: "DE".to_string(),
+        ..Address::default()
+    };
+    let mut builder = InvoiceDataBuilder::new()
+        .invoice_number(format!("INV-{number:05}"))
+        .issue_date("2024-05-01")
+        .due_date("2024-05-31")
+        .currency("EUR")
+        .supplier("Acme GmbH", address("Hauptstrasse 1", "Berlin"))
+        .customer("Kunde AG", address("Nebenweg 7", "Hamburg"));
+    for line in 1..=5 {
+        builder = builder.add_line(format!("Consulting, phase {line}"), "2", "50.00", "100.00");
+    }
+    builder
+        .totals("500.00", "95.00", "595.00")
+        .payment_account("DE89370400440532013000", "COBADEFFXXX")
+        .build()
+}
+
+fn render(c: &mut Criterion) {
+    let invoice = sample_invoice(1);
+    c.bench_function("create_invoice_pdf_bytes", |b| {
+        b.iter(|| create_invoice_pdf_bytes(black_box(&invoice)).expect("render invoice"))
+    });
+
+    // Long wrapped descriptions spill over several pages and stress the
+    // text measuring and wrapping.
+    let mut long = sample_invoice(2);
+    long.lines.extend((1..=200).map(|line| InvoiceLine {
+        description: format!("Line {line}: ") + &"on-site consulting and travel ".repeat(6),
+        quantity: "1".to_string(),
+        unit_price: "50.00".to_string(),
+        total: "50.00".to_string(),
+        ..InvoiceLine::default()
+    }));
+    c.bench_function("create_invoice_pdf_bytes long", |b| {
+        b.iter(|| create_invoice_pdf_bytes(black_box(&long)).expect("render invoice"))
+    });
+
+    let batch: Vec<_> = (1..=1000).map(sample_invoice).collect();
+    let mut group = c.benchmark_group("batch");
+    group.sample_size(10);
+    group.bench_function("1000 invoices", |b| {
+        b.iter(|| {
+            for invoice in &batch {
+                black_box(create_invoice_pdf_bytes(invoice).expect("render invoice"));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
@@ -0,0 +1,275 @@
+//! Fluent construction of [`InvoiceData`] for callers that render invoices
+//! from their own data instead of XML.
+
+use crate::{Address, AllowanceCharge, Contact, InvoiceData, InvoiceLine, TaxSubtotal};
+
+/// Builds an [`InvoiceData`] field by field; anything not set stays empty.
+///
+/// Amounts are strings in XML decimal notation (`"1234.50"`), exactly as the
+/// parsers produce them, and are formatted when rendered.
+///
+/// ```
+/// use ruble::{Address, InvoiceDataBuilder, create_invoice_pdf_bytes};
+///
+/// let invoice = InvoiceDataBuilder::new()
+///     .invoice_number("2024-0042")
+///     .issue_date("2024-05-01")
+///     .currency("EUR")
+///     .supplier("Acme GmbH", Address {
+///         street: "Hauptstrasse 1".to_string(),
+///         postal: "10115".to_string(),
+///         city: "Berlin".to_string(),
+///         ..Address::default()
+///     })
+///     .customer("Kunde AG", Address::default())
+///     .add_line("Consulting", "2", "50.00", "100.00")
+///     .totals("100.00", "19.00", "119.00")
+///     .build();
+///
+/// let pdf = create_invoice_pdf_bytes(&invoice)?;
+/// assert!(pdf.starts_with(b"%PDF-"));
+/// # Ok::<(), ruble::InvoiceError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct InvoiceDataBuilder {
+    data: InvoiceData,
+}
+
+impl Default for InvoiceDataBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InvoiceDataBuilder {
+    pub fn new() -> Self {
+        Self {
+            data: InvoiceData {
+                document_type: "Invoice".to_string(),
+                ..InvoiceData::default()
+            },
+        }
+    }
+
+    /// Renders the document as a credit note instead of an invoice.
+    pub fn credit_note(mut self) -> Self {
+        self.data.document_type = "CreditNote".to_string();
+        self
+    }
+
+    /// The UNTDID 1001 document type code, e.g. "384" for a corrected invoice.
+    pub fn type_code(mut self, code: impl Into<String>) -> Self {
+        self.data.type_code = code.into();
+        self
+    }
+
+    pub fn invoice_number(mut self, number: impl Into<String>) -> Self {
+        self.data.invoice_number = number.into();
+        self
+    }
+
+    pub fn issue_date(mut self, date: impl Into<String>) -> Self {
+        self.data.issue_date = date.into();
+        self
+    }
+
+    pub fn delivery_date(mut self, date: impl Into<String>) -> Self {
+        self.data.delivery_date = date.into();
+        self
+    }
+
+    /// The VAT tax point date, shown when it differs from the issue date.
+    pub fn tax_point_date(mut self, date: impl Into<String>) -> Self {
+        self.data.tax_point_date = date.into();
+        self
+    }
+
+    /// The invoicing period, e.g. the month a subscription covers.
+    pub fn invoice_period(mut self, start: impl Into<String>, end: impl Into<String>) -> Self {
+        self.data.period_start = start.into();
+        self.data.period_end = end.into();
+        self
+    }
+
+    pub fn due_date(mut self, date: impl Into<String>) -> Self {
+        self.data.due_date = date.into();
+        self
+    }
+
+    pub fn payment_terms(mut self, terms: impl Into<String>) -> Self {
+        self.data.payment_terms = terms.into();
+        self
+    }
+
+    pub fn currency(mut self, code: impl Into<String>) -> Self {
+        self.data.currency = code.into();
+        self
+    }
+
+    pub fn buyer_reference(mut self, reference: impl Into<String>) -> Self {
+        self.data.buyer_reference = reference.into();
+        self
+    }
+
+    pub fn order_reference(mut self, reference: impl Into<String>) -> Self {
+        self.data.order_reference = reference.into();
+        self
+    }
+
+    /// The invoice a credit note or corrected invoice refers back to.
+    pub fn preceding_invoice_reference(mut self, reference: impl Into<String>) -> Self {
+        self.data.preceding_invoice_reference = reference.into();
+        self
+    }
+
+    pub fn supplier(mut self, name: impl Into<String>, address: Address) -> Self {
+        self.data.supplier_name = name.into();
+        self.data.supplier_address = address;
+        self
+    }
+
+    /// The registered name, printed under the trading name when it differs.
+    pub fn supplier_legal_name(mut self, name: impl Into<String>) -> Self {
+        self.data.supplier_legal_name = name.into();
+        self
+    }
+
+    pub fn supplier_vat(mut self, vat: impl Into<String>) -> Self {
+        self.data.supplier_vat = vat.into();
+        self
+    }
+
+    pub fn supplier_contact(mut self, contact: Contact) -> Self {
+        self.data.supplier_contact = Some(contact);
+        self
+    }
+
+    pub fn customer(mut self, name: impl Into<String>, address: Address) -> Self {
+        self.data.customer_name = name.into();
+        self.data.customer_address = address;
+        self
+    }
+
+    pub fn customer_legal_name(mut self, name: impl Into<String>) -> Self {
+        self.data.customer_legal_name = name.into();
+        self
+    }
+
+    pub fn customer_vat(mut self, vat: impl Into<String>) -> Self {
+        self.data.customer_vat = vat.into();
+        self
+    }
+
+    /// Bank account for the payment section and the EPC QR code.
+    pub fn payment_account(mut self, iban: impl Into<String>, bic: impl Into<String>) -> Self {
+        self.data.payment_iban = iban.into();
+        self.data.payment_bic = bic.into();
+        self
+    }
+
+    /// Adds a line with just a description and its amounts.
+    pub fn add_line(
+        self,
+        description: impl Into<String>,
+        quantity: impl Into<String>,
+        unit_price: impl Into<String>,
+        total: impl Into<String>,
+    ) -> Self {
+        self.line(InvoiceLine {
+            description: description.into(),
+            quantity: quantity.into(),
+            unit_price: unit_price.into(),
+            total: total.into(),
+            ..InvoiceLine::default()
+        })
+    }
+
+    /// Adds a fully specified line, e.g. with a unit code or VAT rate.
+    pub fn line(mut self, line: InvoiceLine) -> Self {
+        self.data.lines.push(line);
+        self
+    }
+
+    /// Sets the net subtotal, the VAT total and the VAT-inclusive total.
+    pub fn totals(
+        mut self,
+        subtotal: impl Into<String>,
+        tax_total: impl Into<String>,
+        total: impl Into<String>,
+    ) -> Self {
+        self.data.subtotal = subtotal.into();
+        self.data.tax_total = tax_total.into();
+        self.data.total = total.into();
+        self
+    }
+
+    /// Sets a deposit already paid (BT-113), deducted from the amount due.
+    pub fn prepaid_amount(mut self, amount: impl Into<String>) -> Self {
+        self.data.prepaid_amount = amount.into();
+        self
+    }
+
+    /// Sets the cash rounding (BT-114) and the resulting amount due (BT-115).
+    pub fn amount_due(mut self, rounding: impl Into<String>, payable: impl Into<String>) -> Self {
+        self.data.rounding_amount = rounding.into();
+        self.data.payable_amount = payable.into();
+        self
+    }
+
+    pub fn add_tax_subtotal(mut self, subtotal: TaxSubtotal) -> Self {
+        self.data.tax_breakdown.push(subtotal);
+        self
+    }
+
+    pub fn add_allowance_charge(mut self, entry: AllowanceCharge) -> Self {
+        self.data.allowances_charges.push(entry);
+        self
+    }
+
+    pub fn add_note(mut self, note: impl Into<String>) -> Self {
+        self.data.notes.push(note.into());
+        self
+    }
+
+    /// Links an externally hosted supporting document by its URI.
+    pub fn add_external_reference(mut self, uri: impl Into<String>) -> Self {
+        self.data.external_references.push(uri.into());
+        self
+    }
+
+    pub fn build(self) -> InvoiceData {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate_invoice;
+
+    #[test]
+    fn builds_a_valid_invoice() {
+        let data = InvoiceDataBuilder::new()
+            .invoice_number("B-1")
+            .issue_date("2024-05-01")
+            .currency("EUR")
+            .supplier("Acme GmbH", Address::default())
+            .customer("Kunde AG", Address::default())
+            .add_line("Consulting", "2", "50.00", "100.00")
+            .add_note("Thank you")
+            .build();
+
+        assert_eq!(data.document_type, "Invoice");
+        assert_eq!(data.lines[0].description, "Consulting");
+        assert!(data.lines[0].unit_code.is_empty());
+        assert_eq!(data.notes, ["Thank you"]);
+        assert_eq!(validate_invoice(&data), Ok(()));
+        assert_eq!(
+            InvoiceDataBuilder::new()
+                .credit_note()
+                .build()
+                .document_type,
+            "CreditNote"
+        );
+    }
+}
@@ -0,0 +1,545 @@
+//! UN/CEFACT Cross Industry Invoice (Factur-X / ZUGFeRD) parsing.
+
+use roxmltree::Node;
+
+use crate::{
+    Address, AllowanceCharge, InvoiceData, InvoiceError, InvoiceLine, TaxSubtotal,
+    amount_currencies, country_name, find_child, find_child_text, find_children, find_descendant,
+    find_text, parse_xml, validate,
+};
+
+pub fn parse_cii_invoice(xml: &str) -> Result<InvoiceData, InvoiceError> {
+    let doc = parse_xml(xml)?;
+    Ok(cii_invoice_from_root(doc.root_element()))
+}
+
+pub(crate) fn cii_invoice_from_root(root: Node<'_, '_>) -> InvoiceData {
+    let context = find_descendant(root, "ExchangedDocumentContext");
+    let context_id = |parameter| {
+        context
+            .and_then(|node| find_child(node, parameter))
+            .and_then(|node| find_child_text(&node, "ID"))
+            .unwrap_or_default()
+    };
+    let customization_id = context_id("GuidelineSpecifiedDocumentContextParameter");
+    let profile_id = context_id("BusinessProcessSpecifiedDocumentContextParameter");
+    let document = find_descendant(root, "ExchangedDocument");
+    let invoice_number = document
+        .as_ref()
+        .and_then(|node| find_child_text(node, "ID"))
+        .unwrap_or_default();
+    let issue_date = document
+        .and_then(|node| find_descendant(node, "IssueDateTime"))
+        .map(parse_date_time)
+        .unwrap_or_default();
+    let type_code = document
+        .and_then(|node| find_child_text(&node, "TypeCode"))
+        .unwrap_or_default();
+    // UNTDID 1001 code 381 is a credit note; everything else renders as an invoice.
+    let document_type = if type_code == "381" {
+        "CreditNote"
+    } else {
+        "Invoice"
+    }
+    .to_string();
+    let notes = document
+        .map(|node| {
+            node.children()
+                .filter(|child| child.is_element() && child.tag_name().name() == "IncludedNote")
+                .filter_map(|note| find_child_text(&note, "Content"))
+                .filter(|text| !text.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let agreement = find_descendant(root, "ApplicableHeaderTradeAgreement");
+    let (supplier_name, supplier_vat, supplier_address) =
+        parse_trade_party(agreement.and_then(|node| find_child(node, "SellerTradeParty")));
+    let (customer_name, customer_vat, customer_address) =
+        parse_trade_party(agreement.and_then(|node| find_child(node, "BuyerTradeParty")));
+    let buyer_reference = agreement
+        .and_then(|node| find_child_text(&node, "BuyerReference"))
+        .unwrap_or_default();
+    let order_reference = agreement
+        .and_then(|node| find_child(node, "BuyerOrderReferencedDocument"))
+        .and_then(|node| find_child_text(&node, "IssuerAssignedID"))
+        .unwrap_or_default();
+
+    let delivery_date = find_descendant(root, "ApplicableHeaderTradeDelivery")
+        .and_then(|node| find_child(node, "ActualDeliverySupplyChainEvent"))
+        .and_then(|node| find_child(node, "OccurrenceDateTime"))
+        .map(parse_date_time)
+        .unwrap_or_default();
+
+    let settlement = find_descendant(root, "ApplicableHeaderTradeSettlement");
+    let currency = settlement
+        .and_then(|node| find_child_text(&node, "InvoiceCurrencyCode"))
+        .unwrap_or_default();
+    let preceding_invoice_reference = settlement
+        .and_then(|node| find_child(node, "InvoiceReferencedDocument"))
+        .and_then(|node| find_child_text(&node, "IssuerAssignedID"))
+        .unwrap_or_default();
+    let period = settlement.and_then(|node| find_child(node, "BillingSpecifiedPeriod"));
+    let period_date = |name| {
+        period
+            .and_then(|node| find_child(node, name))
+            .map(parse_date_time)
+            .unwrap_or_default()
+    };
+    let period_start = period_date("StartDateTime");
+    let period_end = period_date("EndDateTime");
+    let tax_point_date = settlement
+        .and_then(|node| {
+            find_children(node, "ApplicableTradeTax")
+                .find_map(|tax| find_child(tax, "TaxPointDate"))
+        })
+        .map(parse_date_time)
+        .unwrap_or_default();
+    let due_date = settlement
+        .and_then(|node| find_descendant(node, "DueDateDateTime"))
+        .map(parse_date_time)
+        .unwrap_or_default();
+    let payment_terms = settlement
+        .and_then(|node| {
+            find_children(node, "SpecifiedTradePaymentTerms")
+                .find_map(|terms| find_child_text(&terms, "Description"))
+        })
+        .unwrap_or_default();
+    let tax_breakdown = settlement
+        .map(|node| {
+            find_children(node, "ApplicableTradeTax")
+                .map(parse_trade_tax)
+                .collect()
+        })
+        .unwrap_or_default();
+    let (payment_iban, payment_bic) = settlement.map(parse_payment_account).unwrap_or_default();
+    let allowances_charges = settlement
+        .map(|node| {
+            find_children(node, "SpecifiedTradeAllowanceCharge")
+                .map(parse_allowance_charge)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let summation = settlement
+        .and_then(|node| find_child(node, "SpecifiedTradeSettlementHeaderMonetarySummation"));
+    let subtotal = summation
+        .and_then(|node| find_child_text(&node, "TaxBasisTotalAmount"))
+        .unwrap_or_default();
+    // TaxTotalAmount may be repeated in the tax currency; prefer the invoice currency.
+    let tax_total = summation
+        .and_then(|node| {
+            let amounts: Vec<Node> = find_children(node, "TaxTotalAmount").collect();
+            amounts
+                .iter()
+                .find(|amount| amount.attribute("currencyID") == Some(currency.as_str()))
+                .or(amounts.first())
+                .and_then(|amount| amount.text())
+                .map(|text| text.trim().to_string())
+        })
+        .unwrap_or_default();
+    let total = summation
+        .and_then(|node| find_child_text(&node, "GrandTotalAmount"))
+        .unwrap_or_default();
+    let prepaid_amount = summation
+        .and_then(|node| find_child_text(&node, "TotalPrepaidAmount"))
+        .unwrap_or_default();
+    let rounding_amount = summation
+        .and_then(|node| find_child_text(&node, "RoundingAmount"))
+        .unwrap_or_default();
+    let payable_amount = summation
+        .and_then(|node| find_child_text(&node, "DuePayableAmount"))
+        .unwrap_or_default();
+    // TaxTotalAmount is left out: it may legitimately be in the tax currency.
+    let total_currencies = amount_currencies(
+        summation,
+        &[
+            "TaxBasisTotalAmount",
+            "GrandTotalAmount",
+            "DuePayableAmount",
+        ],
+    );
+
+    let lines = root
+        .descendants()
+        .filter(|node| {
+            node.is_element() && node.tag_name().name() == "IncludedSupplyChainTradeLineItem"
+        })
+        .map(|node| {
+            let product = find_child(node, "SpecifiedTradeProduct");
+            let quantity = find_descendant(node, "BilledQuantity");
+            let tax = find_child(node, "SpecifiedLineTradeSettlement")
+                .and_then(|settlement| find_child(settlement, "ApplicableTradeTax"));
+            InvoiceLine {
+                name: product
+                    .and_then(|product| find_child_text(&product, "Name"))
+                    .unwrap_or_default(),
+                description: product
+                    .and_then(|product| find_child_text(&product, "Description"))
+                    .unwrap_or_default(),
+                quantity: quantity
+                    .and_then(|node| node.text())
+                    .map(|text| text.trim().to_string())
+                    .unwrap_or_default(),
+                unit_code: quantity
+                    .and_then(|node| node.attribute("unitCode"))
+                    .unwrap_or_default()
+                    .to_string(),
+                unit_price: find_descendant(node, "NetPriceProductTradePrice")
+                    .and_then(|price| find_text(&price, "ChargeAmount"))
+                    .unwrap_or_default(),
+                total: find_text(&node, "LineTotalAmount").unwrap_or_default(),
+                tax_percent: tax
+                    .and_then(|tax| find_child_text(&tax, "RateApplicablePercent"))
+                    .unwrap_or_default(),
+                tax_category: tax
+                    .and_then(|tax| find_child_text(&tax, "CategoryCode"))
+                    .unwrap_or_default(),
+                allowances_charges: find_child(node, "SpecifiedLineTradeSettlement")
+                    .map(|settlement| {
+                        find_children(settlement, "SpecifiedTradeAllowanceCharge")
+                            .map(parse_allowance_charge)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                order_line_id: find_child(node, "SpecifiedLineTradeAgreement")
+                    .and_then(|agreement| find_child(agreement, "BuyerOrderReferencedDocument"))
+                    .and_then(|order| find_child_text(&order, "LineID"))
+                    .unwrap_or_default(),
+                seller_item_id: product
+                    .and_then(|product| find_child_text(&product, "SellerAssignedID"))
+                    .unwrap_or_default(),
+                buyer_item_id: product
+                    .and_then(|product| find_child_text(&product, "BuyerAssignedID"))
+                    .unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    InvoiceData {
+        document_type,
+        type_code,
+        customization_id,
+        profile_id,
+        invoice_number,
+        issue_date,
+        delivery_date,
+        tax_point_date,
+        period_start,
+        period_end,
+        due_date,
+        payment_terms,
+        currency,
+        buyer_reference,
+        order_reference,
+        preceding_invoice_reference,
+        supplier_legal_name: supplier_name.clone(),
+        supplier_name,
+        supplier_vat,
+        supplier_address,
+        supplier_contact: None,
+        supplier_endpoint: None,
+        customer_legal_name: customer_name.clone(),
+        customer_name,
+        customer_vat,
+        customer_address,
+        customer_endpoint: None,
+        subtotal,
+        allowances_charges,
+        tax_total,
+        tax_breakdown,
+        total,
+        prepaid_amount,
+        rounding_amount,
+        payable_amount,
+        total_currencies,
+        payment_iban,
+        payment_bic,
+        lines,
+        notes,
+        external_references: crate::external_reference_uris(root),
+    }
+}
+
+fn parse_trade_party(party: Option<Node<'_, '_>>) -> (String, String, Address) {
+    let Some(party) = party else {
+        return (String::new(), String::new(), Address::default());
+    };
+
+    let name = find_child_text(&party, "Name").unwrap_or_default();
+
+    // Prefer the VAT registration ("VA") over a local fiscal number ("FC").
+    let registrations: Vec<Node> = find_children(party, "SpecifiedTaxRegistration")
+        .filter_map(|node| find_child(node, "ID"))
+        .collect();
+    let vat = registrations
+        .iter()
+        .find(|id| id.attribute("schemeID") == Some("VA"))
+        .or(registrations.first())
+        .and_then(|id| id.text())
+        .map(|text| text.trim().to_string())
+        .unwrap_or_default();
+
+    let address_node = find_child(party, "PostalTradeAddress");
+    let address_text = |name| {
+        address_node
+            .and_then(|node| find_child_text(&node, name))
+            .unwrap_or_default()
+    };
+    let address = Address {
+        street: address_text("LineOne"),
+        city: address_text("CityName"),
+        postal: address_text("PostcodeCode"),
+        additional_street: address_text("LineTwo"),
+        building_number: String::new(),
+        country: {
+            let code = address_text("CountryID");
+            address_node
+                .and_then(|node| find_child_text(&node, "CountryName"))
+                .or_else(|| country_name(&code).map(str::to_string))
+                .unwrap_or(code)
+        },
+    };
+
+    (name, vat, address)
+}
+
+/// Returns the payee IBAN and BIC, preferring a credit-transfer payment means.
+fn parse_payment_account(settlement: Node<'_, '_>) -> (String, String) {
+    let means: Vec<Node> =
+        find_children(settlement, "SpecifiedTradeSettlementPaymentMeans").collect();
+    let Some(means) = means
+        .iter()
+        .find(|node| {
+            matches!(
+                find_child_text(node, "TypeCode").as_deref(),
+                Some("30" | "58")
+            )
+        })
+        .or(means.first())
+    else {
+        return (String::new(), String::new());
+    };
+
+    let iban = find_child(*means, "PayeePartyCreditorFinancialAccount")
+        .and_then(|account| find_child_text(&account, "IBANID"))
+        .unwrap_or_default();
+    validate::warn_on_invalid_iban(&iban);
+    let bic = find_child(*means, "PayeeSpecifiedCreditorFinancialInstitution")
+        .and_then(|institution| find_child_text(&institution, "BICID"))
+        .unwrap_or_default();
+    (iban, bic)
+}
+
+fn parse_trade_tax(node: Node<'_, '_>) -> TaxSubtotal {
+    TaxSubtotal {
+        taxable_amount: find_child_text(&node, "BasisAmount").unwrap_or_default(),
+        tax_amount: find_child_text(&node, "CalculatedAmount").unwrap_or_default(),
+        percent: find_child_text(&node, "RateApplicablePercent").unwrap_or_default(),
+        category: find_child_text(&node, "CategoryCode").unwrap_or_default(),
+        exemption_reason: find_child_text(&node, "ExemptionReason").unwrap_or_default(),
+        exemption_reason_code: find_child_text(&node, "ExemptionReasonCode").unwrap_or_default(),
+    }
+}
+
+fn parse_allowance_charge(node: Node<'_, '_>) -> AllowanceCharge {
+    AllowanceCharge {
+        is_charge: find_text(&node, "Indicator")
+            .is_some_and(|value| value.eq_ignore_ascii_case("true")),
+        amount: find_child_text(&node, "ActualAmount").unwrap_or_default(),
+        reason: find_child_text(&node, "Reason").unwrap_or_default(),
+    }
+}
+
+/// Reads a `udt:DateTimeString` (or the `udt:DateString` of a tax point
+/// date), normalising format 102 (`YYYYMMDD`) to the ISO `YYYY-MM-DD` form
+/// used by UBL.
+fn parse_date_time(node: Node<'_, '_>) -> String {
+    let Some(child) =
+        find_descendant(node, "DateTimeString").or_else(|| find_descendant(node, "DateString"))
+    else {
+        return String::new();
+    };
+    let value = child.text().unwrap_or_default().trim();
+    let is_102 = child
+        .attribute("format")
+        .is_none_or(|format| format == "102");
+    if is_102 && value.len() == 8 && value.bytes().all(|byte| byte.is_ascii_digit()) {
+        format!("{}-{}-{}", &value[..4], &value[4..6], &value[6..])
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_CII: &str = r#"
+<rsm:CrossIndustryInvoice xmlns:rsm="urn:un:unece:uncefact:data:standard:CrossIndustryInvoice:100"
+ xmlns:ram="urn:un:unece:uncefact:data:standard:ReusableAggregateBusinessInformationEntity:100"
+ xmlns:udt="urn:un:unece:uncefact:data:standard:UnqualifiedDataType:100">
+  <rsm:ExchangedDocumentContext>
+    <ram:GuidelineSpecifiedDocumentContextParameter>
+      <ram:ID>urn:cen.eu:en16931:2017</ram:ID>
+    </ram:GuidelineSpecifiedDocumentContextParameter>
+  </rsm:ExchangedDocumentContext>
+  <rsm:ExchangedDocument>
+    <ram:ID>RE-2024-001</ram:ID>
+    <ram:TypeCode>380</ram:TypeCode>
+    <ram:IssueDateTime>
+      <udt:DateTimeString format="102">20240315</udt:DateTimeString>
+    </ram:IssueDateTime>
+  </rsm:ExchangedDocument>
+  <rsm:SupplyChainTradeTransaction>
+    <ram:IncludedSupplyChainTradeLineItem>
+      <ram:AssociatedDocumentLineDocument>
+        <ram:LineID>1</ram:LineID>
+      </ram:AssociatedDocumentLineDocument>
+      <ram:SpecifiedTradeProduct>
+        <ram:Name>Schraube M8</ram:Name>
+      </ram:SpecifiedTradeProduct>
+      <ram:SpecifiedLineTradeAgreement>
+        <ram:NetPriceProductTradePrice>
+          <ram:ChargeAmount>2.50</ram:ChargeAmount>
+        </ram:NetPriceProductTradePrice>
+      </ram:SpecifiedLineTradeAgreement>
+      <ram:SpecifiedLineTradeDelivery>
+        <ram:BilledQuantity unitCode="C62">4</ram:BilledQuantity>
+      </ram:SpecifiedLineTradeDelivery>
+      <ram:SpecifiedLineTradeSettlement>
+        <ram:ApplicableTradeTax>
+          <ram:TypeCode>VAT</ram:TypeCode>
+          <ram:CategoryCode>S</ram:CategoryCode>
+          <ram:RateApplicablePercent>19.00</ram:RateApplicablePercent>
+        </ram:ApplicableTradeTax>
+        <ram:SpecifiedTradeSettlementLineMonetarySummation>
+          <ram:LineTotalAmount>10.00</ram:LineTotalAmount>
+        </ram:SpecifiedTradeSettlementLineMonetarySummation>
+      </ram:SpecifiedLineTradeSettlement>
+    </ram:IncludedSupplyChainTradeLineItem>
+    <ram:ApplicableHeaderTradeAgreement>
+      <ram:SellerTradeParty>
+        <ram:Name>Lieferant GmbH</ram:Name>
+        <ram:PostalTradeAddress>
+          <ram:PostcodeCode>10115</ram:PostcodeCode>
+          <ram:LineOne>Hauptstrasse 1</ram:LineOne>
+          <ram:CityName>Berlin</ram:CityName>
+          <ram:CountryID>DE</ram:CountryID>
+        </ram:PostalTradeAddress>
+        <ram:SpecifiedTaxRegistration>
+          <ram:ID schemeID="FC">201/113/40209</ram:ID>
+        </ram:SpecifiedTaxRegistration>
+        <ram:SpecifiedTaxRegistration>
+          <ram:ID schemeID="VA">DE123456789</ram:ID>
+        </ram:SpecifiedTaxRegistration>
+      </ram:SellerTradeParty>
+      <ram:BuyerTradeParty>
+        <ram:Name>Kunde AG</ram:Name>
+        <ram:PostalTradeAddress>
+          <ram:PostcodeCode>80331</ram:PostcodeCode>
+          <ram:LineOne>Marienplatz 2</ram:LineOne>
+          <ram:CityName>Muenchen</ram:CityName>
+          <ram:CountryID>DE</ram:CountryID>
+        </ram:PostalTradeAddress>
+      </ram:BuyerTradeParty>
+    </ram:ApplicableHeaderTradeAgreement>
+    <ram:ApplicableHeaderTradeDelivery>
+      <ram:ActualDeliverySupplyChainEvent>
+        <ram:OccurrenceDateTime>
+          <udt:DateTimeString format="102">20240312</udt:DateTimeString>
+        </ram:OccurrenceDateTime>
+      </ram:ActualDeliverySupplyChainEvent>
+    </ram:ApplicableHeaderTradeDelivery>
+    <ram:ApplicableHeaderTradeSettlement>
+      <ram:InvoiceCurrencyCode>EUR</ram:InvoiceCurrencyCode>
+      <ram:ApplicableTradeTax>
+        <ram:CalculatedAmount>1.90</ram:CalculatedAmount>
+        <ram:TypeCode>VAT</ram:TypeCode>
+        <ram:BasisAmount>10.00</ram:BasisAmount>
+        <ram:CategoryCode>S</ram:CategoryCode>
+        <ram:RateApplicablePercent>19</ram:RateApplicablePercent>
+        <ram:TaxPointDate>
+          <udt:DateString format="102">20240310</udt:DateString>
+        </ram:TaxPointDate>
+      </ram:ApplicableTradeTax>
+      <ram:SpecifiedTradeSettlementPaymentMeans>
+        <ram:TypeCode>58</ram:TypeCode>
+        <ram:PayeePartyCreditorFinancialAccount>
+          <ram:IBANID>DE02120300000000202051</ram:IBANID>
+        </ram:PayeePartyCreditorFinancialAccount>
+        <ram:PayeeSpecifiedCreditorFinancialInstitution>
+          <ram:BICID>BYLADEM1001</ram:BICID>
+        </ram:PayeeSpecifiedCreditorFinancialInstitution>
+      </ram:SpecifiedTradeSettlementPaymentMeans>
+      <ram:BillingSpecifiedPeriod>
+        <ram:StartDateTime>
+          <udt:DateTimeString format="102">20240301</udt:DateTimeString>
+        </ram:StartDateTime>
+        <ram:EndDateTime>
+          <udt:DateTimeString format="102">20240331</udt:DateTimeString>
+        </ram:EndDateTime>
+      </ram:BillingSpecifiedPeriod>
+      <ram:SpecifiedTradePaymentTerms>
+        <ram:Description>Net 14 days</ram:Description>
+        <ram:DueDateDateTime>
+          <udt:DateTimeString format="102">20240414</udt:DateTimeString>
+        </ram:DueDateDateTime>
+      </ram:SpecifiedTradePaymentTerms>
+      <ram:SpecifiedTradeSettlementHeaderMonetarySummation>
+        <ram:LineTotalAmount>10.00</ram:LineTotalAmount>
+        <ram:TaxBasisTotalAmount>10.00</ram:TaxBasisTotalAmount>
+        <ram:TaxTotalAmount currencyID="EUR">1.90</ram:TaxTotalAmount>
+        <ram:GrandTotalAmount>11.90</ram:GrandTotalAmount>
+        <ram:DuePayableAmount>11.90</ram:DuePayableAmount>
+      </ram:SpecifiedTradeSettlementHeaderMonetarySummation>
+    </ram:ApplicableHeaderTradeSettlement>
+  </rsm:SupplyChainTradeTransaction>
+</rsm:CrossIndustryInvoice>
+"#;
+
+    #[test]
+    fn parses_cii_invoice_fields() {
+        let data = parse_cii_invoice(SAMPLE_CII).expect("parse CII invoice");
+        assert_eq!(data.invoice_number, "RE-2024-001");
+        assert_eq!(data.issue_date, "2024-03-15");
+        assert_eq!(data.customization_id, "urn:cen.eu:en16931:2017");
+        assert!(data.profile_id.is_empty());
+        assert_eq!(data.delivery_date, "2024-03-12");
+        assert_eq!(data.tax_point_date, "2024-03-10");
+        assert_eq!(data.period_start, "2024-03-01");
+        assert_eq!(data.period_end, "2024-03-31");
+        assert_eq!(data.due_date, "2024-04-14");
+        assert_eq!(data.payment_terms, "Net 14 days");
+        assert_eq!(data.currency, "EUR");
+        assert_eq!(data.supplier_name, "Lieferant GmbH");
+        assert_eq!(data.supplier_vat, "DE123456789");
+        assert_eq!(data.supplier_address.city, "Berlin");
+        assert_eq!(data.supplier_address.country, "Germany");
+        assert_eq!(data.customer_name, "Kunde AG");
+        assert_eq!(data.customer_address.street, "Marienplatz 2");
+        assert_eq!(data.subtotal, "10.00");
+        assert_eq!(data.tax_total, "1.90");
+        assert_eq!(data.total, "11.90");
+        assert_eq!(data.payable_amount, "11.90");
+        assert!(!data.has_separate_amount_due());
+        assert_eq!(data.tax_breakdown.len(), 1);
+        assert_eq!(data.tax_breakdown[0].percent, "19");
+        assert_eq!(data.payment_iban, "DE02120300000000202051");
+        assert_eq!(data.payment_bic, "BYLADEM1001");
+        assert_eq!(data.lines.len(), 1);
+        assert_eq!(data.lines[0].name, "Schraube M8");
+        assert!(data.lines[0].description.is_empty());
+        assert_eq!(data.lines[0].quantity, "4");
+        assert_eq!(data.lines[0].unit_code, "C62");
+        assert_eq!(data.lines[0].tax_percent, "19.00");
+        assert_eq!(data.lines[0].unit_price, "2.50");
+        assert_eq!(data.lines[0].total, "10.00");
+    }
+
+    #[test]
+    fn parse_invoice_detects_cii() {
+        let data = crate::parse_invoice(SAMPLE_CII).expect("parse invoice");
+        assert_eq!(data.invoice_number, "RE-2024-001");
+    }
+}
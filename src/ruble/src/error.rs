@@ -0,0 +1,152 @@
+use std::fmt;
+
+/// Errors returned by the library's parsing, extraction, and rendering functions.
+#[derive(Debug)]
+pub enum InvoiceError {
+    /// The input is not well-formed XML.
+    Xml(roxmltree::Error),
+    /// The input is empty or only whitespace.
+    EmptyDocument,
+    /// The root element is neither a UBL nor a CII invoice.
+    UnsupportedDocument(String),
+    /// A field required to process the invoice is absent.
+    MissingField(&'static str),
+    /// An embedded attachment is not valid base64.
+    Base64(base64::DecodeError),
+    /// The PDF could not be assembled.
+    #[cfg(feature = "pdf")]
+    Pdf(printpdf::Error),
+    /// The logo image could not be decoded or is too large.
+    #[cfg(feature = "pdf")]
+    Image(printpdf::image_crate::ImageError),
+    /// The font is not a usable TrueType or OpenType file.
+    #[cfg(feature = "pdf")]
+    Font(ttf_parser::FaceParsingError),
+    /// Text contains a character the font cannot draw, with
+    /// [`crate::InvoiceStyle::strict_fonts`] set.
+    UnsupportedGlyph(char),
+    /// The source XML could not be attached to the rendered PDF.
+    #[cfg(feature = "pdf")]
+    EmbedXml(printpdf::lopdf::Error),
+    /// The payment QR code could not be encoded.
+    #[cfg(feature = "pdf")]
+    QrCode(qrcode::types::QrError),
+    /// Reading or writing a file failed.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for InvoiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Xml(err) => {
+                // roxmltree puts the position mid-sentence ("unexpected close
+                // tag at 3:5"); lead with it, and leave it out for errors such
+                // as an unclosed root that carry none.
+                let detail = err.to_string();
+                let position = format!(" at {}", err.pos());
+                if detail.contains(&position) {
+                    let detail = detail.replacen(&position, "", 1);
+                    write!(f, "XML parse error at {}: {detail}", err.pos())
+                } else {
+                    write!(f, "XML parse error: {detail}")
+                }
+            }
+            Self::EmptyDocument => write!(f, "empty document"),
+            Self::UnsupportedDocument(root) => {
+                write!(f, "unrecognized invoice root element <{root}>")
+            }
+            Self::MissingField(field) => write!(f, "missing required field {field}"),
+            Self::Base64(err) => write!(f, "decode embedded document: {err}"),
+            #[cfg(feature = "pdf")]
+            Self::Pdf(err) => write!(f, "generate PDF: {err}"),
+            #[cfg(feature = "pdf")]
+            Self::Image(err) => write!(f, "decode logo image: {err}"),
+            #[cfg(feature = "pdf")]
+            Self::Font(err) => write!(f, "load font: {err}"),
+            Self::UnsupportedGlyph(ch) => {
+                write!(
+                    f,
+                    "character {ch:?} ({}) is not in the font",
+                    ch.escape_unicode()
+                )
+            }
+            #[cfg(feature = "pdf")]
+            Self::EmbedXml(err) => write!(f, "attach XML to PDF: {err}"),
+            #[cfg(feature = "pdf")]
+            Self::QrCode(err) => write!(f, "encode payment QR code: {err}"),
+            Self::Io(err) => write!(f, "I/O error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for InvoiceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Base64(err) => Some(err),
+            #[cfg(feature = "pdf")]
+            Self::Pdf(err) => Some(err),
+            #[cfg(feature = "pdf")]
+            Self::Image(err) => Some(err),
+            #[cfg(feature = "pdf")]
+            Self::Font(err) => Some(err),
+            #[cfg(feature = "pdf")]
+            Self::EmbedXml(err) => Some(err),
+            #[cfg(feature = "pdf")]
+            Self::QrCode(err) => Some(err),
+            Self::Io(err) => Some(err),
+            // The XML message already includes roxmltree's detail; returning
+            // it as the source too would print it twice in error chains.
+            Self::Xml(_)
+            | Self::EmptyDocument
+            | Self::UnsupportedDocument(_)
+            | Self::MissingField(_)
+            | Self::UnsupportedGlyph(_) => None,
+        }
+    }
+}
+
+impl From<roxmltree::Error> for InvoiceError {
+    fn from(err: roxmltree::Error) -> Self {
+        Self::Xml(err)
+    }
+}
+
+impl From<base64::DecodeError> for InvoiceError {
+    fn from(err: base64::DecodeError) -> Self {
+        Self::Base64(err)
+    }
+}
+
+#[cfg(feature = "pdf")]
+impl From<printpdf::Error> for InvoiceError {
+    fn from(err: printpdf::Error) -> Self {
+        Self::Pdf(err)
+    }
+}
+
+#[cfg(feature = "pdf")]
+impl From<printpdf::image_crate::ImageError> for InvoiceError {
+    fn from(err: printpdf::image_crate::ImageError) -> Self {
+        Self::Image(err)
+    }
+}
+
+#[cfg(feature = "pdf")]
+impl From<ttf_parser::FaceParsingError> for InvoiceError {
+    fn from(err: ttf_parser::FaceParsingError) -> Self {
+        Self::Font(err)
+    }
+}
+
+#[cfg(feature = "pdf")]
+impl From<qrcode::types::QrError> for InvoiceError {
+    fn from(err: qrcode::types::QrError) -> Self {
+        Self::QrCode(err)
+    }
+}
+
+impl From<std::io::Error> for InvoiceError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
@@ -0,0 +1,123 @@
+//! Factur-X output: the rendered invoice PDF with its source XML attached.
+
+use printpdf::lopdf::{Document, Object, Stream, StringFormat, dictionary};
+
+use crate::pdf::create_invoice_pdf_bytes_styled;
+use crate::{InvoiceData, InvoiceError, InvoiceStyle};
+
+/// Attachment name Factur-X and ZUGFeRD readers look for.
+const FACTURX_FILENAME: &str = "factur-x.xml";
+/// UBL sources are attached too, but readers only pick up CII as Factur-X.
+const UBL_FILENAME: &str = "invoice.xml";
+
+pub fn create_facturx(data: &InvoiceData, xml: &str) -> Result<Vec<u8>, InvoiceError> {
+    create_facturx_styled(data, &InvoiceStyle::default(), xml)
+}
+
+/// Renders the invoice and attaches `xml` as an associated file with the
+/// `/Data` relationship, so one PDF carries both the visual and the
+/// machine-readable invoice. The XMP metadata and output intent that full
+/// PDF/A-3 conformance asks for are not written yet.
+pub fn create_facturx_styled(
+    data: &InvoiceData,
+    style: &InvoiceStyle,
+    xml: &str,
+) -> Result<Vec<u8>, InvoiceError> {
+    let rendered = create_invoice_pdf_bytes_styled(data, style)?;
+    let mut doc = Document::load_mem(&rendered).map_err(InvoiceError::EmbedXml)?;
+    // Associated files (`/AF`) were introduced with PDF 2.0 and PDF/A-3,
+    // which is based on PDF 1.7.
+    doc.version = "1.7".to_string();
+
+    let source = crate::parse_xml(xml)?;
+    let filename = match source.root_element().tag_name().name() {
+        "CrossIndustryInvoice" => FACTURX_FILENAME,
+        _ => UBL_FILENAME,
+    };
+    let text = |value: &str| Object::String(value.as_bytes().to_vec(), StringFormat::Literal);
+
+    let embedded = doc.add_object(Stream::new(
+        dictionary! {
+            "Type" => "EmbeddedFile",
+            "Subtype" => Object::Name(b"text/xml".to_vec()),
+            "Params" => dictionary! { "Size" => xml.len() as i64 },
+        },
+        xml.as_bytes().to_vec(),
+    ));
+    let filespec = doc.add_object(dictionary! {
+        "Type" => "Filespec",
+        "F" => text(filename),
+        "UF" => text(filename),
+        "Desc" => text("Invoice XML"),
+        "AFRelationship" => "Data",
+        "EF" => dictionary! { "F" => embedded, "UF" => embedded },
+    });
+
+    let catalog = doc.catalog_mut().map_err(InvoiceError::EmbedXml)?;
+    catalog.set(
+        "Names",
+        dictionary! {
+            "EmbeddedFiles" => dictionary! {
+                "Names" => vec![text(filename), filespec.into()],
+            },
+        },
+    );
+    catalog.set("AF", vec![Object::from(filespec)]);
+
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_ubl_invoice;
+    use crate::tests::SAMPLE_XML;
+
+    #[test]
+    fn attaches_the_source_xml_as_associated_file() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let bytes = create_facturx(&data, SAMPLE_XML).expect("create factur-x");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        assert_eq!(pdf.version, "1.7");
+
+        let catalog = pdf.catalog().expect("catalog");
+        let af = catalog.get(b"AF").and_then(Object::as_array).expect("AF");
+        let filespec_id = af[0].as_reference().expect("filespec reference");
+        let filespec = pdf.get_dictionary(filespec_id).expect("filespec");
+        assert_eq!(
+            filespec
+                .get(b"AFRelationship")
+                .and_then(Object::as_name)
+                .ok(),
+            Some(&b"Data"[..])
+        );
+        assert_eq!(
+            filespec.get(b"UF").and_then(Object::as_str).ok(),
+            Some(UBL_FILENAME.as_bytes())
+        );
+
+        let names = catalog
+            .get(b"Names")
+            .and_then(Object::as_dict)
+            .and_then(|names| names.get(b"EmbeddedFiles"))
+            .and_then(Object::as_dict)
+            .and_then(|tree| tree.get(b"Names"))
+            .and_then(Object::as_array)
+            .expect("embedded files name tree");
+        assert_eq!(names[1].as_reference().expect("reference"), filespec_id);
+
+        let stream_id = filespec
+            .get(b"EF")
+            .and_then(Object::as_dict)
+            .and_then(|ef| ef.get(b"F"))
+            .and_then(Object::as_reference)
+            .expect("embedded file reference");
+        let stream = pdf
+            .get_object(stream_id)
+            .and_then(Object::as_stream)
+            .expect("embedded file stream");
+        assert_eq!(stream.content, SAMPLE_XML.as_bytes());
+    }
+}
@@ -0,0 +1,392 @@
+//! Display formatting for amounts and dates.
+
+/// Formats a raw XML amount for display as `"{currency} 1,234.50"`: two
+/// decimals (rounded half away from zero) with comma thousands grouping.
+/// A comma decimal separator (`12,00`) is accepted as well. Values that are
+/// not plain decimals are shown verbatim; empty input stays empty.
+pub fn format_amount(raw: &str, currency: &str) -> String {
+    format_amount_with(raw, currency, 2, 2)
+}
+
+/// Like [`format_amount`], rounding to `max_decimals` and then dropping
+/// trailing zeros down to `min_decimals`, so `10.005` with 2..=4 stays
+/// `10.005` while `10` becomes `10.00`.
+pub fn format_amount_with(
+    raw: &str,
+    currency: &str,
+    min_decimals: usize,
+    max_decimals: usize,
+) -> String {
+    let amount = format_number(raw, min_decimals, max_decimals);
+    if amount.is_empty() || currency.is_empty() {
+        amount
+    } else {
+        format!("{currency} {amount}")
+    }
+}
+
+/// Like [`format_amount_with`], but shows the symbol of a currency known to
+/// [`currency_symbol`] instead of its code. `locale` decides where it goes:
+/// before the amount in English (`€1,234.50`, `-$2.00`), after it in French
+/// and German (`1,234.50 €`). Unknown currencies keep the code.
+pub fn format_amount_with_symbol(
+    raw: &str,
+    currency: &str,
+    min_decimals: usize,
+    max_decimals: usize,
+    locale: &str,
+) -> String {
+    let Some(symbol) = currency_symbol(currency) else {
+        return format_amount_with(raw, currency, min_decimals, max_decimals);
+    };
+    let amount = format_number(raw, min_decimals, max_decimals);
+    if amount.is_empty() {
+        return amount;
+    }
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    match language.to_ascii_lowercase().as_str() {
+        "fr" | "de" => format!("{amount} {symbol}"),
+        _ => match amount.strip_prefix('-') {
+            Some(unsigned) => format!("-{symbol}{unsigned}"),
+            None => format!("{symbol}{amount}"),
+        },
+    }
+}
+
+/// Symbol of a major ISO 4217 currency, limited to ones the built-in PDF
+/// fonts can draw. Dollar currencies other than USD carry a prefix so they
+/// stay distinguishable.
+pub fn currency_symbol(code: &str) -> Option<&'static str> {
+    let symbol = match code.trim().to_ascii_uppercase().as_str() {
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "JPY" => "¥",
+        "CNY" => "CN¥",
+        "CAD" => "CA$",
+        "AUD" => "A$",
+        "NZD" => "NZ$",
+        "HKD" => "HK$",
+        "SGD" => "S$",
+        "MXN" => "MX$",
+        "BRL" => "R$",
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// The number part of [`format_amount_with`]: empty for empty input and the
+/// trimmed input itself when it is not a plain decimal.
+fn format_number(raw: &str, min_decimals: usize, max_decimals: usize) -> String {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return String::new();
+    }
+    let decimal = format_decimal(raw, max_decimals)
+        .or_else(|| format_decimal(&comma_decimal(raw)?, max_decimals));
+    match decimal {
+        Some(mut amount) => {
+            if let Some(point) = amount.find('.') {
+                let keep = point + 1 + min_decimals;
+                while amount.len() > keep && amount.ends_with('0') {
+                    amount.pop();
+                }
+                if amount.ends_with('.') {
+                    amount.pop();
+                }
+            }
+            amount
+        }
+        None => raw.to_string(),
+    }
+}
+
+/// Reformats an ISO `YYYY-MM-DD` date with a strftime-like `pattern`:
+/// `%Y` (2024), `%y` (24), `%m` (01), `%-m` (1), `%d` (01), `%-d` / `%e`
+/// (1), `%B` (January) and `%%`. Month names follow `locale` (`en`, `fr`
+/// or `de`; anything else falls back to English). Values that are not ISO
+/// dates are returned unchanged.
+pub fn format_date(raw: &str, pattern: &str, locale: &str) -> String {
+    let Some((year, month, day)) = parse_iso_date(raw.trim()) else {
+        return raw.to_string();
+    };
+    let months = month_names(locale);
+    let month_name = months[month as usize - 1];
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            out.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{year:04}")),
+            Some('y') => out.push_str(&format!("{:02}", year % 100)),
+            Some('m') => out.push_str(&format!("{month:02}")),
+            Some('d') => out.push_str(&format!("{day:02}")),
+            Some('e') => out.push_str(&day.to_string()),
+            Some('-') => match chars.next() {
+                Some('d') => out.push_str(&day.to_string()),
+                Some('m') => out.push_str(&month.to_string()),
+                other => {
+                    out.push_str("%-");
+                    out.extend(other);
+                }
+            },
+            Some('B') => out.push_str(month_name),
+            Some('%') => out.push('%'),
+            other => {
+                out.push('%');
+                out.extend(other);
+            }
+        }
+    }
+    out
+}
+
+fn parse_iso_date(raw: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = raw.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let all_digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if !all_digits(year) || !all_digits(month) || !all_digits(day) {
+        return None;
+    }
+    let (year, month, day) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((year, month, day))
+}
+
+fn month_names(locale: &str) -> [&'static str; 12] {
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    match language.to_ascii_lowercase().as_str() {
+        "fr" => [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ],
+        "de" => [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ],
+        _ => [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ],
+    }
+}
+
+/// Reads a raw XML amount as a number, also accepting a comma decimal
+/// separator. Non-numeric and non-finite values yield `None`.
+pub(crate) fn parse_amount(raw: &str) -> Option<f64> {
+    let raw = raw.trim();
+    raw.parse()
+        .ok()
+        .or_else(|| comma_decimal(raw)?.parse().ok())
+        .filter(|value: &f64| value.is_finite())
+}
+
+/// Rewrites `12,00` as `12.00`. Not schema-valid, but some suppliers send
+/// it; anything with a dot or several commas is left alone.
+fn comma_decimal(raw: &str) -> Option<String> {
+    (!raw.contains('.') && raw.matches(',').count() == 1).then(|| raw.replace(',', "."))
+}
+
+/// Reads an amount whose last `.` or `,` is the decimal separator, taking
+/// any earlier ones as thousands grouping, e.g. `1.234,56`. Also returns
+/// the number of decimals written after the separator.
+pub(crate) fn parse_grouped_amount(raw: &str) -> Option<(f64, usize)> {
+    let raw = raw.trim();
+    let (units, fraction) = match raw.rfind(['.', ',']) {
+        Some(at) => (&raw[..at], &raw[at + 1..]),
+        None => (raw, ""),
+    };
+    let units = units.replace(['.', ','], "");
+    let value = parse_amount(&format!("{units}.{fraction}"))?;
+    Some((value, fraction.len()))
+}
+
+fn format_decimal(raw: &str, decimals: usize) -> Option<String> {
+    let (negative, unsigned) = match raw.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, raw.strip_prefix('+').unwrap_or(raw)),
+    };
+    let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if int_part.is_empty() && frac_part.is_empty()
+        || !int_part.bytes().all(|byte| byte.is_ascii_digit())
+        || !frac_part.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        return None;
+    }
+
+    // Work on the digit string directly so large amounts never lose precision.
+    let mut digits: Vec<u8> = int_part.bytes().map(|byte| byte - b'0').collect();
+    if digits.is_empty() {
+        digits.push(0);
+    }
+    let mut frac: Vec<u8> = frac_part.bytes().map(|byte| byte - b'0').collect();
+    let round_up = frac.get(decimals).is_some_and(|digit| *digit >= 5);
+    frac.resize(decimals, 0);
+    digits.extend(frac);
+    if round_up {
+        let mut index = digits.len();
+        loop {
+            if index == 0 {
+                digits.insert(0, 1);
+                break;
+            }
+            index -= 1;
+            if digits[index] == 9 {
+                digits[index] = 0;
+            } else {
+                digits[index] += 1;
+                break;
+            }
+        }
+    }
+    let (int_digits, frac_digits) = digits.split_at(digits.len() - decimals);
+
+    let first_significant = int_digits
+        .iter()
+        .position(|digit| *digit != 0)
+        .unwrap_or(int_digits.len() - 1);
+    let int_digits = &int_digits[first_significant..];
+    let mut out = String::new();
+    if negative && digits.iter().any(|digit| *digit != 0) {
+        out.push('-');
+    }
+    for (index, digit) in int_digits.iter().enumerate() {
+        if index > 0 && (int_digits.len() - index) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(char::from(b'0' + digit));
+    }
+    if decimals > 0 {
+        out.push('.');
+        out.extend(frac_digits.iter().map(|digit| char::from(b'0' + digit)));
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_amounts_with_grouping_and_two_decimals() {
+        assert_eq!(format_amount("1234.5", "EUR"), "EUR 1,234.50");
+        assert_eq!(format_amount("1234567.891", "USD"), "USD 1,234,567.89");
+        assert_eq!(format_amount("999.995", "EUR"), "EUR 1,000.00");
+        assert_eq!(format_amount("-2", "EUR"), "EUR -2.00");
+        assert_eq!(format_amount(".5", ""), "0.50");
+        assert_eq!(format_amount("-0.001", "EUR"), "EUR 0.00");
+        assert_eq!(format_amount(" 12 ", "EUR"), "EUR 12.00");
+        assert_eq!(format_amount("n/a", "EUR"), "EUR n/a");
+        assert_eq!(format_amount("", "EUR"), "");
+    }
+
+    #[test]
+    fn accepts_comma_decimal_separators() {
+        assert_eq!(format_amount("12,00", "EUR"), "EUR 12.00");
+        assert_eq!(format_amount("1234,5", "EUR"), "EUR 1,234.50");
+        assert_eq!(format_amount("1,234,5", "EUR"), "EUR 1,234,5");
+        assert_eq!(parse_amount("12,5"), Some(12.5));
+        assert_eq!(parse_amount(" 7.25 "), Some(7.25));
+        assert_eq!(parse_amount("1.234,5"), None);
+        assert_eq!(parse_amount("inf"), None);
+        assert_eq!(parse_grouped_amount("1.234,56"), Some((1234.56, 2)));
+        assert_eq!(parse_grouped_amount("1,234.5"), Some((1234.5, 1)));
+        assert_eq!(parse_grouped_amount("12"), Some((12.0, 0)));
+        assert_eq!(parse_grouped_amount("n/a"), None);
+    }
+
+    #[test]
+    fn reformats_iso_dates_with_a_pattern() {
+        assert_eq!(format_date("2024-01-01", "%d/%m/%Y", "en"), "01/01/2024");
+        assert_eq!(
+            format_date("2024-01-01", "%-d %B %Y", "fr"),
+            "1 janvier 2024"
+        );
+        assert_eq!(
+            format_date("2024-03-09", "%e. %B %Y", "de-DE"),
+            "9. März 2024"
+        );
+        assert_eq!(
+            format_date("2024-12-31", "%B %d, %y", "xx"),
+            "December 31, 24"
+        );
+        assert_eq!(
+            format_date("2024-12-31", "100%% %Y %Q", "en"),
+            "100% 2024 %Q"
+        );
+        assert_eq!(
+            format_date("2024-02-30T10:00", "%d/%m/%Y", "en"),
+            "2024-02-30T10:00"
+        );
+        assert_eq!(format_date("31.12.2024", "%Y", "en"), "31.12.2024");
+        assert_eq!(format_date("", "%Y", "en"), "");
+    }
+
+    #[test]
+    fn keeps_extra_price_decimals_up_to_the_maximum() {
+        assert_eq!(format_amount_with("10.005", "EUR", 2, 4), "EUR 10.005");
+        assert_eq!(format_amount_with("10", "EUR", 2, 4), "EUR 10.00");
+        assert_eq!(format_amount_with("0.123456", "", 2, 4), "0.1235");
+        assert_eq!(format_amount_with("1234.5", "", 0, 0), "1,235");
+        assert_eq!(format_amount_with("1234.5", "", 3, 3), "1,234.500");
+    }
+
+    #[test]
+    fn shows_known_currency_symbols_where_the_locale_puts_them() {
+        assert_eq!(currency_symbol("EUR"), Some("€"));
+        assert_eq!(currency_symbol("USD"), Some("$"));
+        assert_eq!(currency_symbol("gbp"), Some("£"));
+        assert_eq!(currency_symbol("CHF"), None);
+
+        assert_eq!(
+            format_amount_with_symbol("1234.5", "EUR", 2, 2, "en"),
+            "€1,234.50"
+        );
+        assert_eq!(format_amount_with_symbol("12", "USD", 2, 2, "en"), "$12.00");
+        assert_eq!(format_amount_with_symbol("-2", "GBP", 2, 2, "en"), "-£2.00");
+        assert_eq!(
+            format_amount_with_symbol("12", "EUR", 2, 2, "de-DE"),
+            "12.00 €"
+        );
+        assert_eq!(
+            format_amount_with_symbol("12", "CHF", 2, 2, "en"),
+            "CHF 12.00"
+        );
+        assert_eq!(format_amount_with_symbol("", "EUR", 2, 2, "en"), "");
+    }
+}
@@ -0,0 +1,339 @@
+//! Self-contained HTML rendering that mirrors the PDF layout.
+
+use std::fmt::Write;
+
+use crate::validate::iban_display;
+use crate::{
+    InvoiceData, InvoiceLine, address_lines, adjustment_label, distinct_legal_name, format_amount,
+    is_web_link,
+};
+
+const STYLESHEET: &str = "\
+body { font-family: Helvetica, Arial, sans-serif; font-size: 10pt; color: #000; \
+max-width: 174mm; margin: 12mm auto; }
+h1 { color: #24384f; font-size: 22pt; margin: 0 0 8mm; }
+h2 { font-size: 11pt; margin: 0 0 2mm; }
+.marker { color: #b31a1a; font-size: 12pt; }
+section { border-bottom: 0.3mm solid #b3b3b3; padding: 3mm 0; }
+.columns { display: flex; gap: 10mm; }
+.columns > div { flex: 1; }
+p { margin: 0 0 1.5mm; }
+.small { font-size: 9.5pt; }
+table { width: 100%; border-collapse: collapse; font-size: 9pt; }
+th { color: #595959; text-align: left; border-bottom: 0.3mm solid #b3b3b3; padding: 1mm 0; }
+td { vertical-align: top; padding: 1mm 0; }
+.num { text-align: right; white-space: nowrap; padding-left: 4mm; }
+.description { font-size: 8pt; }
+.totals { margin-left: auto; width: 62mm; }
+.totals td, .totals th { padding: 0.5mm 0; }
+.total { font-weight: bold; font-size: 12pt; }
+";
+
+/// Renders the invoice as a standalone HTML document with inline styles,
+/// covering the same sections as [`crate::create_invoice_pdf_bytes`].
+pub fn render_invoice_html(data: &InvoiceData) -> String {
+    let title = data.title();
+    let amount = |raw: &str| escape(&format_amount(raw, &data.currency));
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(
+        html,
+        "<title>{title} {}</title>",
+        escape(&data.invoice_number)
+    );
+    let _ = writeln!(html, "<style>\n{STYLESHEET}</style>\n</head>\n<body>");
+    if data.is_corrected() {
+        let _ = writeln!(
+            html,
+            "<h1>{title} <span class=\"marker\">CORRECTED</span></h1>"
+        );
+    } else {
+        let _ = writeln!(html, "<h1>{title}</h1>");
+    }
+
+    html.push_str("<section>\n<h2>Invoice details</h2>\n<div class=\"columns\">\n<div>\n");
+    paragraph(&mut html, "Invoice Number", &data.invoice_number);
+    paragraph(&mut html, "Order Reference", &data.order_reference);
+    paragraph(&mut html, "Buyer Reference", &data.buyer_reference);
+    paragraph(
+        &mut html,
+        "Relates to invoice",
+        &data.preceding_invoice_reference,
+    );
+    html.push_str("</div>\n<div>\n");
+    paragraph(&mut html, "Issue Date", &data.issue_date);
+    paragraph(&mut html, "Delivery Date", &data.delivery_date);
+    paragraph(&mut html, "Tax Point Date", data.distinct_tax_point_date());
+    if !data.period_start.is_empty() && !data.period_end.is_empty() {
+        let period = format!("{} – {}", data.period_start, data.period_end);
+        paragraph(&mut html, "Period", &period);
+    }
+    paragraph(&mut html, "Due Date", &data.due_date);
+    paragraph(&mut html, "Payment Terms", &data.payment_terms);
+    html.push_str("</div>\n</div>\n</section>\n");
+
+    html.push_str("<section class=\"columns\">\n");
+    let parties = [
+        (
+            "Supplier",
+            &data.supplier_name,
+            &data.supplier_legal_name,
+            &data.supplier_address,
+            &data.supplier_vat,
+            data.supplier_contact.as_ref(),
+        ),
+        (
+            "Customer",
+            &data.customer_name,
+            &data.customer_legal_name,
+            &data.customer_address,
+            &data.customer_vat,
+            None,
+        ),
+    ];
+    for (heading, name, legal_name, address, vat, contact) in parties {
+        let _ = writeln!(html, "<div>\n<h2>{heading}</h2>\n<p>{}</p>", escape(name));
+        if let Some(legal_name) = distinct_legal_name(name, legal_name) {
+            let _ = writeln!(html, "<p class=\"small\">{}</p>", escape(legal_name));
+        }
+        for line in address_lines(address) {
+            let _ = writeln!(html, "<p class=\"small\">{}</p>", escape(&line));
+        }
+        if !data.supplier_vat.is_empty() || !data.customer_vat.is_empty() {
+            let _ = writeln!(html, "<p class=\"small\">VAT: {}</p>", escape(vat));
+        }
+        if let Some(contact) = contact {
+            let _ = writeln!(
+                html,
+                "<p class=\"small\">Contact: {}</p>",
+                escape(&contact.summary())
+            );
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</section>\n");
+
+    if !data.payment_iban.is_empty() || !data.payment_bic.is_empty() {
+        html.push_str("<section>\n<h2>Payment</h2>\n<div class=\"columns small\">\n<div>\n");
+        if !data.payment_iban.is_empty() {
+            paragraph(&mut html, "IBAN", &iban_display(&data.payment_iban));
+        }
+        html.push_str("</div>\n<div>\n");
+        paragraph(&mut html, "BIC", &data.payment_bic);
+        html.push_str("</div>\n</div>\n</section>\n");
+    }
+
+    html.push_str("<section>\n<h2>Items</h2>\n<table>\n<thead><tr><th>Description</th>");
+    html.push_str("<th class=\"num\">Qty</th><th class=\"num\">Unit</th>");
+    html.push_str(
+        "<th class=\"num\">VAT%</th><th class=\"num\">Total</th></tr></thead>\n<tbody>\n",
+    );
+    for line in &data.lines {
+        let rate = if line.tax_percent.is_empty() {
+            String::new()
+        } else {
+            format!("{}%", line.tax_percent)
+        };
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td>\
+             <td class=\"num\">{}</td><td class=\"num\">{}</td></tr>",
+            item_cell(line),
+            escape(&line.quantity_with_unit()),
+            amount(&line.unit_price),
+            escape(&rate),
+            amount(&line.total),
+        );
+    }
+    html.push_str("</tbody>\n</table>\n</section>\n");
+
+    html.push_str("<section>\n<table class=\"totals\">\n");
+    total_row(&mut html, "", "Subtotal:", &amount(&data.subtotal));
+    for entry in &data.allowances_charges {
+        let (label, sign) = if entry.is_charge {
+            ("Charge", "")
+        } else {
+            ("Discount", "-")
+        };
+        let label = if entry.reason.is_empty() {
+            label
+        } else {
+            &entry.reason
+        };
+        let value = amount(&format!("{sign}{}", entry.amount));
+        total_row(&mut html, "", &format!("{}:", escape(label)), &value);
+    }
+    total_row(&mut html, "", "VAT:", &amount(&data.tax_total));
+    if !data.tax_breakdown.is_empty() {
+        html.push_str("<tr><th>Rate</th><th class=\"num\">Taxable</th>");
+        html.push_str("<th class=\"num\">VAT</th></tr>\n");
+        for subtotal in &data.tax_breakdown {
+            let rate = match (subtotal.percent.is_empty(), subtotal.category.is_empty()) {
+                (false, false) => format!("{}% ({})", subtotal.percent, subtotal.category),
+                (false, true) => format!("{}%", subtotal.percent),
+                (true, _) => subtotal.category.clone(),
+            };
+            let _ = writeln!(
+                html,
+                "<tr class=\"small\"><td>{}</td><td class=\"num\">{}</td>\
+                 <td class=\"num\">{}</td></tr>",
+                escape(&rate),
+                amount(&subtotal.taxable_amount),
+                amount(&subtotal.tax_amount),
+            );
+            if !subtotal.exemption().is_empty() {
+                let _ = writeln!(
+                    html,
+                    "<tr class=\"small\"><td colspan=\"3\">— {}</td></tr>",
+                    escape(subtotal.exemption())
+                );
+            }
+        }
+    }
+    if data.has_separate_amount_due() {
+        total_row(&mut html, "", "Total:", &amount(&data.total));
+        if data.has_prepaid() {
+            let value = amount(&format!("-{}", data.prepaid_amount));
+            total_row(&mut html, "", "Prepaid:", &value);
+        }
+        if data.has_rounding() {
+            total_row(&mut html, "", "Rounding:", &amount(&data.rounding_amount));
+        }
+        total_row(
+            &mut html,
+            " class=\"total\"",
+            "Amount due:",
+            &amount(&data.amount_due()),
+        );
+    } else {
+        total_row(
+            &mut html,
+            " class=\"total\"",
+            "Total:",
+            &amount(&data.total),
+        );
+    }
+    html.push_str("</table>\n</section>\n");
+
+    if !data.notes.is_empty() {
+        html.push_str("<section>\n<h2>Notes</h2>\n");
+        for note in &data.notes {
+            let _ = writeln!(html, "<p class=\"small\">{}</p>", escape(note));
+        }
+        html.push_str("</section>\n");
+    }
+
+    if !data.external_references.is_empty() {
+        html.push_str("<section>\n<h2>References</h2>\n");
+        for uri in &data.external_references {
+            let link = is_web_link(uri);
+            let uri = escape(uri);
+            if link {
+                let _ = writeln!(html, "<p class=\"small\"><a href=\"{uri}\">{uri}</a></p>");
+            } else {
+                let _ = writeln!(html, "<p class=\"small\">{uri}</p>");
+            }
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Writes `label: value`, skipping empty values like the PDF header does.
+fn paragraph(html: &mut String, label: &str, value: &str) {
+    if !value.is_empty() {
+        let _ = writeln!(html, "<p>{label}: {}</p>", escape(value));
+    }
+}
+
+/// Totals rows span the label across the breakdown's first two columns.
+fn total_row(html: &mut String, class: &str, label: &str, value: &str) {
+    let _ = writeln!(
+        html,
+        "<tr{class}><td colspan=\"2\">{label}</td><td class=\"num\">{value}</td></tr>"
+    );
+}
+
+/// The item name in bold above the description, or whichever is present,
+/// followed by line discounts and charges and the purchase order line.
+fn item_cell(line: &InvoiceLine) -> String {
+    let mut cell = match (line.name.is_empty(), line.description.is_empty()) {
+        (true, _) => escape(&line.with_seller_item_id(&line.description)),
+        (false, true) => format!(
+            "<strong>{}</strong>",
+            escape(&line.with_seller_item_id(&line.name))
+        ),
+        (false, false) => format!(
+            "<strong>{}</strong><div class=\"description\">{}</div>",
+            escape(&line.with_seller_item_id(&line.name)),
+            escape(&line.description)
+        ),
+    };
+    for entry in &line.allowances_charges {
+        let label = adjustment_label(entry, &format_amount(&entry.amount, ""));
+        let _ = write!(cell, "<div class=\"description\">{}</div>", escape(&label));
+    }
+    if !line.order_line_id.is_empty() {
+        let _ = write!(
+            cell,
+            "<div class=\"description\">PO line: {}</div>",
+            escape(&line.order_line_id)
+        );
+    }
+    cell
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_ubl_invoice;
+    use crate::tests::SAMPLE_XML;
+
+    #[test]
+    fn renders_invoice_number_and_every_line() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let mut second = data.lines[0].clone();
+        second.description = "Gadget <large> & \"shiny\"".to_string();
+        data.lines.push(second);
+
+        let html = render_invoice_html(&data);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("Invoice Number: INV-1"));
+        assert!(html.contains(">Widget<"));
+        assert!(html.contains("Gadget &lt;large&gt; &amp; &quot;shiny&quot;"));
+        assert!(!html.contains("<large>"));
+    }
+
+    #[test]
+    fn links_only_web_references() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.external_references = vec![
+            "HTTPS://example.com/timesheet.pdf".to_string(),
+            " javascript:alert(document.cookie)".to_string(),
+        ];
+
+        let html = render_invoice_html(&data);
+        assert!(html.contains("<a href=\"HTTPS://example.com/timesheet.pdf\">"));
+        assert!(html.contains("javascript:alert(document.cookie)"));
+        assert!(!html.contains("href=\" javascript:"));
+        assert!(!html.contains("href=\"javascript:"));
+    }
+}
@@ -1,85 +1,429 @@
-use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
 
-use anyhow::{Context, Result};
 use base64::Engine;
-use printpdf::{
-    BuiltinFont, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point, Rgb,
-};
 use roxmltree::{Document, Node};
 
-#[derive(Debug, Clone)]
+mod builder;
+mod cii;
+mod error;
+#[cfg(feature = "pdf")]
+mod facturx;
+mod format;
+mod html;
+#[cfg(feature = "pdf")]
+mod pdf;
+mod validate;
+
+pub use builder::InvoiceDataBuilder;
+pub use cii::parse_cii_invoice;
+pub use error::InvoiceError;
+#[cfg(feature = "pdf")]
+pub use facturx::{create_facturx, create_facturx_styled};
+pub use format::{
+    currency_symbol, format_amount, format_amount_with, format_amount_with_symbol, format_date,
+};
+pub use html::render_invoice_html;
+#[cfg(feature = "pdf")]
+pub use pdf::{
+    create_invoice_pdf_bytes, create_invoice_pdf_bytes_styled, validate_font, validate_logo,
+    write_invoice_pdf, write_invoice_pdf_styled, InvoiceStyle, PageSize, Theme,
+};
+#[cfg(all(feature = "pdf", feature = "std-fs"))]
+pub use pdf::{create_invoice_pdf, create_invoice_pdf_styled};
+pub use validate::{check_currencies, check_totals, iban_is_valid, validate_invoice};
+
+/// UBL basic components (`cbc:`), the namespace of every leaf value we read.
+const CBC_NS: &str = "urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2";
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Address {
     pub street: String,
     pub city: String,
     pub postal: String,
+    pub additional_street: String,
+    pub building_number: String,
+    pub country: String,
 }
 
-#[derive(Debug, Clone)]
+impl Address {
+    /// The street name followed by the building number, e.g. "Keizersgracht 12".
+    pub fn street_line(&self) -> String {
+        match (self.street.is_empty(), self.building_number.is_empty()) {
+            (_, true) => self.street.clone(),
+            (true, false) => self.building_number.clone(),
+            (false, false) => format!("{} {}", self.street, self.building_number),
+        }
+    }
+}
+
+/// A party's contact point (BG-6); any of the fields may be empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Contact {
+    pub name: String,
+    pub telephone: String,
+    pub email: String,
+}
+
+impl Contact {
+    /// The non-empty fields joined for a single line, e.g. "Jane Doe, +49 30 1234".
+    pub fn summary(&self) -> String {
+        [&self.name, &self.telephone, &self.email]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// A party's electronic address (BT-34 / BT-49) used for PEPPOL routing.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Endpoint {
+    pub value: String,
+    /// ISO 6523 ICD or EAS code from `schemeID`, e.g. `0088` for GLN.
+    pub scheme: String,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct InvoiceLine {
+    /// Short item name (BT-153).
+    pub name: String,
+    /// Longer item description (BT-154).
     pub description: String,
     pub quantity: String,
+    /// UN/ECE Recommendation 20 unit code of the quantity, e.g. `HUR`.
+    pub unit_code: String,
     pub unit_price: String,
     pub total: String,
+    /// VAT rate of the line (BT-152), e.g. `19.00`.
+    pub tax_percent: String,
+    /// VAT category code of the line (BT-151), e.g. `S`.
+    pub tax_category: String,
+    /// Line of the buyer's purchase order this line fulfils (BT-132).
+    pub order_line_id: String,
+    /// The seller's identifier for the item (BT-155), e.g. a SKU.
+    pub seller_item_id: String,
+    /// The buyer's identifier for the item (BT-156).
+    pub buyer_item_id: String,
+    /// Discounts and surcharges on this line alone (BG-27, BG-28), already
+    /// included in its total.
+    pub allowances_charges: Vec<AllowanceCharge>,
+}
+
+impl InvoiceLine {
+    /// Quantity followed by its unit, e.g. "3 hrs"; unknown codes are shown as is.
+    pub fn quantity_with_unit(&self) -> String {
+        if self.unit_code.is_empty() {
+            return self.quantity.clone();
+        }
+        let unit = unit_label(&self.unit_code).unwrap_or(&self.unit_code);
+        format!("{} {unit}", self.quantity)
+    }
+
+    /// `text` prefixed with the seller's item ID when there is one, e.g.
+    /// "[SKU-123] Widget".
+    pub fn with_seller_item_id(&self, text: &str) -> String {
+        if self.seller_item_id.is_empty() {
+            text.to_string()
+        } else {
+            format!("[{}] {text}", self.seller_item_id)
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AllowanceCharge {
+    pub is_charge: bool,
+    pub amount: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TaxSubtotal {
+    pub taxable_amount: String,
+    pub tax_amount: String,
+    pub percent: String,
+    pub category: String,
+    /// Why the category is exempt or reverse charged (BT-120), e.g.
+    /// "Reverse charge, Art. 196".
+    pub exemption_reason: String,
+    /// Code for the exemption (BT-121), e.g. `VATEX-EU-AE`.
+    pub exemption_reason_code: String,
+}
+
+impl TaxSubtotal {
+    /// The exemption text to print: the reason, else its code.
+    pub fn exemption(&self) -> &str {
+        if self.exemption_reason.is_empty() {
+            &self.exemption_reason_code
+        } else {
+            &self.exemption_reason
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct InvoiceData {
+    /// "Invoice" or "CreditNote".
+    pub document_type: String,
+    /// Document type code (BT-3, UNTDID 1001), e.g. 380 for a commercial
+    /// invoice or 384 for a corrected one; empty when absent.
+    pub type_code: String,
+    /// Specification the document claims to follow (BT-24), e.g. the PEPPOL
+    /// BIS Billing 3.0 customization URN.
+    pub customization_id: String,
+    /// Business process the document belongs to (BT-23), e.g.
+    /// `urn:fdc:peppol.eu:2017:poacc:billing:01:1.0`.
+    pub profile_id: String,
     pub invoice_number: String,
     pub issue_date: String,
+    /// Actual delivery date (BT-72), the VAT point of supply when it differs
+    /// from the issue date.
+    pub delivery_date: String,
+    /// Date the VAT becomes accountable (BT-7), when it differs from the
+    /// issue date.
+    pub tax_point_date: String,
+    /// Start of the invoicing period (BT-73), e.g. a subscription month.
+    pub period_start: String,
+    /// End of the invoicing period (BT-74).
+    pub period_end: String,
     pub due_date: String,
+    /// Human-readable payment terms (BT-20), e.g. "Net 30".
+    pub payment_terms: String,
     pub currency: String,
+    /// BT-10, required by XRechnung (Leitweg-ID for German public buyers).
+    pub buyer_reference: String,
+    /// BT-13, the buyer's purchase order number.
+    pub order_reference: String,
+    /// BT-25, the invoice a credit note or corrected invoice refers back to.
+    pub preceding_invoice_reference: String,
     pub supplier_name: String,
+    /// Registered legal name (BT-27, `PartyLegalEntity/RegistrationName`),
+    /// which may differ from the trading name in `supplier_name`.
+    pub supplier_legal_name: String,
     pub supplier_vat: String,
     pub supplier_address: Address,
+    /// Seller contact (BG-6), `None` when the document has no contact block.
+    pub supplier_contact: Option<Contact>,
+    pub supplier_endpoint: Option<Endpoint>,
     pub customer_name: String,
+    /// Registered legal name of the buyer (BT-44), see `supplier_legal_name`.
+    pub customer_legal_name: String,
     pub customer_vat: String,
     pub customer_address: Address,
+    pub customer_endpoint: Option<Endpoint>,
     pub subtotal: String,
+    pub allowances_charges: Vec<AllowanceCharge>,
     pub tax_total: String,
+    pub tax_breakdown: Vec<TaxSubtotal>,
     pub total: String,
+    /// Deposits already paid (BT-113), empty when absent.
+    pub prepaid_amount: String,
+    /// Cash rounding added to the total (BT-114), empty when absent.
+    pub rounding_amount: String,
+    /// What the buyer still owes (BT-115), after prepayments and rounding.
+    pub payable_amount: String,
+    /// `currencyID` of each document total that carries one, as
+    /// `(element, currency)`, e.g. `("PayableAmount", "EUR")`. Amounts are
+    /// always read in `currency`; [`check_currencies`] reports disagreements.
+    pub total_currencies: Vec<(String, String)>,
+    pub payment_iban: String,
+    pub payment_bic: String,
     pub lines: Vec<InvoiceLine>,
+    /// Document-level free-text notes (BT-22), in document order.
+    pub notes: Vec<String>,
+    /// Links to externally hosted supporting documents (BT-124), see
+    /// [`extract_external_references`]. They are shown, never fetched.
+    pub external_references: Vec<String>,
+}
+
+impl InvoiceData {
+    /// The amount to pay: the payable amount when the document states one,
+    /// otherwise the total less prepayments plus rounding.
+    pub fn amount_due(&self) -> String {
+        if !self.payable_amount.trim().is_empty() {
+            return self.payable_amount.clone();
+        }
+        if !self.has_prepaid() && !self.has_rounding() {
+            return self.total.clone();
+        }
+        let Some(total) = format::parse_amount(&self.total) else {
+            return self.total.clone();
+        };
+        let prepaid = format::parse_amount(&self.prepaid_amount).unwrap_or(0.0);
+        let rounding = format::parse_amount(&self.rounding_amount).unwrap_or(0.0);
+        format!("{:.2}", total - prepaid + rounding)
+    }
+
+    /// The tax point date, or empty when it is missing or matches the issue
+    /// date and so adds nothing to the header.
+    pub fn distinct_tax_point_date(&self) -> &str {
+        if self.tax_point_date == self.issue_date {
+            ""
+        } else {
+            &self.tax_point_date
+        }
+    }
+
+    /// Whether the prepaid amount is present and not zero.
+    pub fn has_prepaid(&self) -> bool {
+        format::parse_amount(&self.prepaid_amount).is_some_and(|value| value != 0.0)
+    }
+
+    /// Whether the rounding amount is present and not zero.
+    pub fn has_rounding(&self) -> bool {
+        format::parse_amount(&self.rounding_amount).is_some_and(|value| value != 0.0)
+    }
+
+    /// Whether the totals need an "Amount due" row below the total, because
+    /// prepayments or rounding make the payable amount differ from it.
+    pub fn has_separate_amount_due(&self) -> bool {
+        self.has_prepaid()
+            || self.has_rounding()
+            || (!self.payable_amount.trim().is_empty()
+                && format::parse_amount(&self.payable_amount) != format::parse_amount(&self.total))
+    }
+
+    /// Describes [`InvoiceData::type_code`], `None` for codes outside the
+    /// common invoice and credit note subtypes.
+    pub fn type_label(&self) -> Option<&'static str> {
+        TYPE_CODE_LABELS
+            .iter()
+            .find(|(code, _)| *code == self.type_code.trim())
+            .map(|(_, label)| *label)
+    }
+
+    /// Heading for the rendered document, from the type code and, for codes
+    /// without a heading of their own, the root element.
+    pub fn title(&self) -> &'static str {
+        match self.type_code.trim() {
+            "381" => "CREDIT NOTE",
+            "261" => "SELF-BILLED CREDIT NOTE",
+            "383" => "DEBIT NOTE",
+            "386" => "PREPAYMENT INVOICE",
+            "389" => "SELF-BILLED INVOICE",
+            _ if self.document_type == "CreditNote" => "CREDIT NOTE",
+            _ => "INVOICE",
+        }
+    }
+
+    /// Whether the document corrects an earlier invoice (code 384).
+    pub fn is_corrected(&self) -> bool {
+        self.type_code.trim() == "384"
+    }
 }
 
+/// UNTDID 1001 codes used for invoices and credit notes.
+const TYPE_CODE_LABELS: &[(&str, &str)] = &[
+    ("261", "Self-billed credit note"),
+    ("326", "Partial invoice"),
+    ("380", "Commercial invoice"),
+    ("381", "Credit note"),
+    ("383", "Debit note"),
+    ("384", "Corrected invoice"),
+    ("386", "Prepayment invoice"),
+    ("389", "Self-billed invoice"),
+    ("751", "Invoice information for accounting purposes"),
+];
+
 #[derive(Debug, Clone)]
 pub struct EmbeddedPdf {
     pub filename: Option<String>,
+    /// The `mimeCode` attribute, e.g. "application/pdf" or "text/csv".
+    pub mime_code: String,
     pub bytes: Vec<u8>,
 }
 
-pub fn parse_ubl_invoice(xml: &str) -> Result<InvoiceData> {
-    let doc = Document::parse(xml).context("parse XML")?;
+/// Parses the XML, reporting a blank input (an empty file from a failed
+/// upload, say) as [`InvoiceError::EmptyDocument`] rather than a parse error.
+pub(crate) fn parse_xml(xml: &str) -> Result<Document<'_>, InvoiceError> {
+    if xml.trim().is_empty() {
+        return Err(InvoiceError::EmptyDocument);
+    }
+    Ok(Document::parse(xml)?)
+}
+
+/// Parses a UBL or CII invoice, choosing the syntax from the root element.
+pub fn parse_invoice(xml: &str) -> Result<InvoiceData, InvoiceError> {
+    let doc = parse_xml(xml)?;
     let root = doc.root_element();
+    match root.tag_name().name() {
+        "Invoice" | "CreditNote" => Ok(ubl_invoice_from_root(root)),
+        "CrossIndustryInvoice" => Ok(cii::cii_invoice_from_root(root)),
+        other => Err(InvoiceError::UnsupportedDocument(other.to_string())),
+    }
+}
+
+pub fn parse_ubl_invoice(xml: &str) -> Result<InvoiceData, InvoiceError> {
+    let doc = parse_xml(xml)?;
+    Ok(ubl_invoice_from_root(doc.root_element()))
+}
 
-    let invoice_number = find_text(&root, "ID").unwrap_or_default();
+fn ubl_invoice_from_root(root: Node<'_, '_>) -> InvoiceData {
+    let is_credit_note = root.tag_name().name() == "CreditNote";
+    let document_type = if is_credit_note { "CreditNote" } else { "Invoice" }.to_string();
+    // The document ID is a direct child; a descendant search could pick up an
+    // ID inside ext:UBLExtensions (e.g. a signature reference) first.
+    let invoice_number = find_child_text_ns(&root, CBC_NS, "ID").unwrap_or_default();
     let issue_date = find_text(&root, "IssueDate").unwrap_or_default();
+    let tax_point_date = find_child_text_ns(&root, CBC_NS, "TaxPointDate").unwrap_or_default();
+    let customization_id = find_child_text_ns(&root, CBC_NS, "CustomizationID").unwrap_or_default();
+    let type_tag = if is_credit_note { "CreditNoteTypeCode" } else { "InvoiceTypeCode" };
+    let type_code = find_child_text_ns(&root, CBC_NS, type_tag).unwrap_or_default();
+    let profile_id = find_child_text_ns(&root, CBC_NS, "ProfileID").unwrap_or_default();
+    let delivery_date = find_child(root, "Delivery")
+        .and_then(|node| find_child_text(&node, "ActualDeliveryDate"))
+        .unwrap_or_default();
+    let period = find_child(root, "InvoicePeriod");
+    let period_date = |name| {
+        period
+            .and_then(|node| find_child_text(&node, name))
+            .unwrap_or_default()
+    };
+    let period_start = period_date("StartDate");
+    let period_end = period_date("EndDate");
     let due_date = find_text(&root, "DueDate").unwrap_or_default();
+    let payment_terms = find_children(root, "PaymentTerms")
+        .find_map(|node| find_child_text(&node, "Note"))
+        .unwrap_or_default();
     let currency = find_text(&root, "DocumentCurrencyCode").unwrap_or_default();
+    let notes = find_children(root, "Note")
+        .filter_map(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect();
+    let buyer_reference = find_child_text(&root, "BuyerReference").unwrap_or_default();
+    let order_reference = find_child(root, "OrderReference")
+        .and_then(|node| find_child_text(&node, "ID"))
+        .unwrap_or_default();
+    let preceding_invoice_reference = find_children(root, "BillingReference")
+        .filter_map(|node| find_child(node, "InvoiceDocumentReference"))
+        .find_map(|node| find_child_text(&node, "ID"))
+        .unwrap_or_default();
 
     let supplier_party =
         find_descendant(root, "AccountingSupplierParty").and_then(|node| find_descendant(node, "Party"));
-    let supplier_name = supplier_party
-        .as_ref()
-        .and_then(|node| find_text(node, "Name"))
-        .unwrap_or_default();
-    let supplier_vat = supplier_party
-        .as_ref()
-        .and_then(|node| find_text(node, "CompanyID"))
-        .unwrap_or_default();
+    let supplier_name = parse_party_name(supplier_party);
+    let supplier_legal_name = supplier_party.map(parse_legal_name).unwrap_or_default();
+    let supplier_vat = parse_party_vat(supplier_party.as_ref());
     let supplier_address = parse_address(supplier_party.as_ref());
+    let supplier_contact = supplier_party.and_then(parse_contact);
+    let supplier_endpoint = supplier_party.and_then(parse_endpoint);
 
     let customer_party =
         find_descendant(root, "AccountingCustomerParty").and_then(|node| find_descendant(node, "Party"));
-    let customer_name = customer_party
-        .as_ref()
-        .and_then(|node| find_text(node, "Name"))
-        .unwrap_or_default();
-    let customer_vat = customer_party
-        .as_ref()
-        .and_then(|node| find_text(node, "CompanyID"))
-        .unwrap_or_default();
+    let customer_name = parse_party_name(customer_party);
+    let customer_legal_name = customer_party.map(parse_legal_name).unwrap_or_default();
+    let customer_vat = parse_party_vat(customer_party.as_ref());
     let customer_address = parse_address(customer_party.as_ref());
+    let customer_endpoint = customer_party.and_then(parse_endpoint);
 
     let legal_total = find_descendant(root, "LegalMonetaryTotal");
     let subtotal = legal_total
@@ -90,358 +434,600 @@ pub fn parse_ubl_invoice(xml: &str) -> Result<InvoiceData> {
         .as_ref()
         .and_then(|node| find_text(node, "TaxInclusiveAmount"))
         .unwrap_or_default();
-    let tax_total = find_text(&root, "TaxAmount").unwrap_or_default();
+    let prepaid_amount = legal_total
+        .as_ref()
+        .and_then(|node| find_child_text(node, "PrepaidAmount"))
+        .unwrap_or_default();
+    let rounding_amount = legal_total
+        .as_ref()
+        .and_then(|node| find_child_text(node, "PayableRoundingAmount"))
+        .unwrap_or_default();
+    let payable_amount = legal_total
+        .as_ref()
+        .and_then(|node| find_child_text(node, "PayableAmount"))
+        .unwrap_or_default();
+    let total_currencies = amount_currencies(
+        legal_total,
+        &["TaxExclusiveAmount", "TaxInclusiveAmount", "PayableAmount"],
+    );
+    let allowances_charges = find_children(root, "AllowanceCharge")
+        .map(parse_allowance_charge)
+        .collect();
+    let tax_amounts: Vec<String> = find_children(root, "TaxTotal")
+        .filter_map(|node| find_child_text(&node, "TaxAmount"))
+        .collect();
+    let tax_total = sum_amounts(&tax_amounts);
+    let tax_breakdown = find_children(root, "TaxTotal")
+        .flat_map(|node| find_children(node, "TaxSubtotal"))
+        .map(parse_tax_subtotal)
+        .collect();
+
+    let (payment_iban, payment_bic) = parse_payment_account(root);
 
+    let (line_tag, quantity_tag) = if is_credit_note {
+        ("CreditNoteLine", "CreditedQuantity")
+    } else {
+        ("InvoiceLine", "InvoicedQuantity")
+    };
     let mut lines = Vec::new();
     for line_node in root
         .descendants()
-        .filter(|node| node.is_element() && node.tag_name().name() == "InvoiceLine")
+        .filter(|node| node.is_element() && node.tag_name().name() == line_tag)
     {
+        let quantity = find_descendant(line_node, quantity_tag);
+        let tax_category = find_descendant(line_node, "ClassifiedTaxCategory");
+        let item = find_child(line_node, "Item");
         let line = InvoiceLine {
-            description: find_text(&line_node, "Description").unwrap_or_default(),
-            quantity: find_text(&line_node, "InvoicedQuantity").unwrap_or_default(),
+            name: item
+                .and_then(|node| find_child_text_ns(&node, CBC_NS, "Name"))
+                .unwrap_or_default(),
+            description: item
+                .and_then(|node| find_child_text(&node, "Description"))
+                .unwrap_or_default(),
+            quantity: quantity
+                .and_then(|node| node.text())
+                .map(|text| text.trim().to_string())
+                .unwrap_or_default(),
+            unit_code: quantity
+                .and_then(|node| node.attribute("unitCode"))
+                .unwrap_or_default()
+                .to_string(),
             unit_price: find_text(&line_node, "PriceAmount").unwrap_or_default(),
             total: find_text(&line_node, "LineExtensionAmount").unwrap_or_default(),
+            tax_percent: tax_category
+                .and_then(|node| find_child_text(&node, "Percent"))
+                .unwrap_or_default(),
+            tax_category: tax_category
+                .and_then(|node| find_child_text_ns(&node, CBC_NS, "ID"))
+                .unwrap_or_default(),
+            order_line_id: find_child(line_node, "OrderLineReference")
+                .and_then(|node| find_child_text(&node, "LineID"))
+                .unwrap_or_default(),
+            seller_item_id: item
+                .and_then(|node| find_child(node, "SellersItemIdentification"))
+                .and_then(|node| find_child_text(&node, "ID"))
+                .unwrap_or_default(),
+            buyer_item_id: item
+                .and_then(|node| find_child(node, "BuyersItemIdentification"))
+                .and_then(|node| find_child_text(&node, "ID"))
+                .unwrap_or_default(),
+            // Direct children only: Price/AllowanceCharge is the price discount
+            // already reflected in the unit price.
+            allowances_charges: find_children(line_node, "AllowanceCharge")
+                .map(parse_allowance_charge)
+                .collect(),
         };
         lines.push(line);
     }
 
-    Ok(InvoiceData {
+    InvoiceData {
+        document_type,
+        type_code,
+        customization_id,
+        profile_id,
         invoice_number,
         issue_date,
+        delivery_date,
+        tax_point_date,
+        period_start,
+        period_end,
         due_date,
+        payment_terms,
         currency,
+        buyer_reference,
+        order_reference,
+        preceding_invoice_reference,
         supplier_name,
+        supplier_legal_name,
         supplier_vat,
         supplier_address,
+        supplier_contact,
+        supplier_endpoint,
         customer_name,
+        customer_legal_name,
         customer_vat,
         customer_address,
+        customer_endpoint,
         subtotal,
+        allowances_charges,
         tax_total,
+        tax_breakdown,
         total,
+        prepaid_amount,
+        rounding_amount,
+        payable_amount,
+        total_currencies,
+        payment_iban,
+        payment_bic,
         lines,
-    })
+        notes,
+        external_references: external_reference_uris(root),
+    }
 }
 
-pub fn extract_embedded_pdf(xml: &str) -> Result<Option<EmbeddedPdf>> {
-    let doc = Document::parse(xml).context("parse XML for embedded PDF")?;
-    let node = doc.descendants().find(|node| {
-        node.is_element()
-            && node.tag_name().name() == "EmbeddedDocumentBinaryObject"
-            && node.attribute("mimeCode") == Some("application/pdf")
-    });
+/// Returns the first embedded attachment with MIME type `application/pdf`.
+pub fn extract_embedded_pdf(xml: &str) -> Result<Option<EmbeddedPdf>, InvoiceError> {
+    let doc = parse_xml(xml)?;
+    let node = embedded_document_nodes(&doc)
+        .find(|node| node.attribute("mimeCode") == Some("application/pdf"));
 
     let Some(node) = node else {
         return Ok(None);
     };
+    decode_embedded_document(node)
+}
+
+/// Returns every embedded attachment in document order, whatever its MIME type.
+/// Attachments with an empty payload are skipped.
+pub fn extract_embedded_documents(xml: &str) -> Result<Vec<EmbeddedPdf>, InvoiceError> {
+    let doc = parse_xml(xml)?;
+    let mut documents = Vec::new();
+    for node in embedded_document_nodes(&doc) {
+        if let Some(document) = decode_embedded_document(node)? {
+            documents.push(document);
+        }
+    }
+    Ok(documents)
+}
+
+/// Returns the URIs of attachments hosted elsewhere, in document order: UBL
+/// `Attachment/ExternalReference/URI` and CII
+/// `AdditionalReferencedDocument/URIID`. Nothing is fetched.
+pub fn extract_external_references(xml: &str) -> Result<Vec<String>, InvoiceError> {
+    let doc = parse_xml(xml)?;
+    Ok(external_reference_uris(doc.root_element()))
+}
+
+pub(crate) fn external_reference_uris(root: Node<'_, '_>) -> Vec<String> {
+    root.descendants()
+        .filter(|node| {
+            let parent = node.parent_element().map(|parent| parent.tag_name().name());
+            match node.tag_name().name() {
+                "URI" => parent == Some("ExternalReference"),
+                "URIID" => parent == Some("AdditionalReferencedDocument"),
+                _ => false,
+            }
+        })
+        .filter_map(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+        .collect()
+}
+
+/// Whether an external reference may be rendered as a clickable link. The
+/// URI comes from the supplier, so anything but `http` and `https` (such as
+/// `javascript:` or `file:`) is only shown as text.
+pub(crate) fn is_web_link(uri: &str) -> bool {
+    let uri = uri.trim();
+    ["http://", "https://"].iter().any(|scheme| {
+        uri.get(..scheme.len())
+            .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+    })
+}
 
-    let payload = node.text().unwrap_or("").trim();
+/// Every leaf element's text keyed by its local tag name, for checking what a
+/// document actually contains when a parsed field comes out empty. Repeated
+/// tags get a `#2`, `#3`, ... suffix in document order, and attachment
+/// payloads are summarized instead of copied.
+pub fn debug_field_map(xml: &str) -> Result<BTreeMap<String, String>, InvoiceError> {
+    let doc = parse_xml(xml)?;
+    let mut fields = BTreeMap::new();
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let leaves = doc
+        .descendants()
+        .filter(|node| node.is_element() && !node.children().any(|child| child.is_element()));
+    for node in leaves {
+        let Some(text) = node.text().map(str::trim).filter(|text| !text.is_empty()) else {
+            continue;
+        };
+        let name = node.tag_name().name();
+        let count = seen.entry(name).or_default();
+        *count += 1;
+        let key = if *count == 1 {
+            name.to_string()
+        } else {
+            format!("{name}#{count}")
+        };
+        let value = if name == "EmbeddedDocumentBinaryObject" {
+            format!("[{} characters of base64]", text.len())
+        } else {
+            text.to_string()
+        };
+        fields.insert(key, value);
+    }
+    Ok(fields)
+}
+
+fn embedded_document_nodes<'a, 'input>(
+    doc: &'a Document<'input>,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    doc.descendants().filter(|node| {
+        node.is_element() && node.tag_name().name() == "EmbeddedDocumentBinaryObject"
+    })
+}
+
+fn decode_embedded_document(node: Node<'_, '_>) -> Result<Option<EmbeddedPdf>, InvoiceError> {
+    // MIME-style base64 is commonly wrapped at 76 columns, so drop any
+    // whitespace before handing the payload to the strict decoder.
+    let payload: String = node
+        .text()
+        .unwrap_or("")
+        .chars()
+        .filter(|ch| !ch.is_ascii_whitespace())
+        .collect();
     if payload.is_empty() {
         return Ok(None);
     }
 
-    let bytes = base64::engine::general_purpose::STANDARD
-        .decode(payload)
-        .context("decode embedded PDF")?;
+    let bytes = base64::engine::general_purpose::STANDARD.decode(payload)?;
     Ok(Some(EmbeddedPdf {
         filename: node.attribute("filename").map(|value| value.to_string()),
+        mime_code: node.attribute("mimeCode").unwrap_or_default().to_string(),
         bytes,
     }))
 }
 
-pub fn create_invoice_pdf(data: &InvoiceData, output_file: &Path) -> Result<()> {
-    let (doc, page1, layer1) =
-        PdfDocument::new("Invoice", Mm(210.0), Mm(297.0), "Layer 1");
-    let font = doc
-        .add_builtin_font(BuiltinFont::Helvetica)
-        .context("load built-in font")?;
-    let font_bold = doc
-        .add_builtin_font(BuiltinFont::HelveticaBold)
-        .context("load bold font")?;
-    let layer = doc.get_page(page1).get_layer(layer1);
-
-    let mut y = 284.0;
-    let line_height = 6.5;
-    let left_x = 18.0;
-    let right_x = 110.0;
-
-    layer.set_fill_color(Color::Rgb(Rgb::new(0.14, 0.22, 0.33, None)));
-    write_text(&layer, &font_bold, 22.0, left_x, y, "INVOICE");
-    layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
-    y -= 10.0;
-
-    write_text(
-        &layer,
-        &font_bold,
-        10.0,
-        left_x,
-        y,
-        "Invoice details",
-    );
-    y -= 6.0;
-    write_text(
-        &layer,
-        &font,
-        10.0,
-        left_x,
-        y,
-        &format!("Invoice Number: {}", data.invoice_number),
-    );
-    write_text(
-        &layer,
-        &font,
-        10.0,
-        right_x,
-        y,
-        &format!("Issue Date: {}", data.issue_date),
-    );
-    y -= line_height;
-    if !data.due_date.is_empty() {
-        write_text(
-            &layer,
-            &font,
-            10.0,
-            right_x,
-            y,
-            &format!("Due Date: {}", data.due_date),
-        );
-    }
-
-    y -= 8.0;
-    draw_divider(&layer, left_x, y, 192.0);
-    y -= 7.0;
-
-    write_text(&layer, &font_bold, 11.0, left_x, y, "Supplier");
-    write_text(&layer, &font_bold, 11.0, right_x, y, "Customer");
-    y -= line_height;
-    write_text(
-        &layer,
-        &font,
-        10.0,
-        left_x,
-        y,
-        &data.supplier_name,
-    );
-    write_text(
-        &layer,
-        &font,
-        10.0,
-        right_x,
-        y,
-        &data.customer_name,
-    );
-    y -= line_height;
-    if !data.supplier_address.street.is_empty() || !data.customer_address.street.is_empty() {
-        write_text(
-            &layer,
-            &font,
-            9.5,
-            left_x,
-            y,
-            &data.supplier_address.street,
-        );
-        write_text(
-            &layer,
-            &font,
-            9.5,
-            right_x,
-            y,
-            &data.customer_address.street,
-        );
-        y -= line_height;
-    }
-    if !data.supplier_address.city.is_empty()
-        || !data.supplier_address.postal.is_empty()
-        || !data.customer_address.city.is_empty()
-        || !data.customer_address.postal.is_empty()
-    {
-        write_text(
-            &layer,
-            &font,
-            9.5,
-            left_x,
-            y,
-            &format!(
-                "{} {}",
-                data.supplier_address.postal, data.supplier_address.city
-            ),
-        );
-        write_text(
-            &layer,
-            &font,
-            9.5,
-            right_x,
-            y,
-            &format!(
-                "{} {}",
-                data.customer_address.postal, data.customer_address.city
-            ),
-        );
-        y -= line_height;
-    }
-    if !data.supplier_vat.is_empty() || !data.customer_vat.is_empty() {
-        write_text(
-            &layer,
-            &font,
-            9.5,
-            left_x,
-            y,
-            &format!("VAT: {}", data.supplier_vat),
-        );
-        write_text(
-            &layer,
-            &font,
-            9.5,
-            right_x,
-            y,
-            &format!("VAT: {}", data.customer_vat),
-        );
-        y -= line_height;
-    }
-
-    y -= 6.0;
-    draw_divider(&layer, left_x, y, 192.0);
-    y -= 7.0;
-
-    write_text(&layer, &font_bold, 11.0, left_x, y, "Items");
-    y -= 6.0;
-    layer.set_fill_color(Color::Rgb(Rgb::new(0.35, 0.35, 0.35, None)));
-    write_text(&layer, &font_bold, 9.5, left_x, y, "Description");
-    write_text(&layer, &font_bold, 9.5, 122.0, y, "Qty");
-    write_text(&layer, &font_bold, 9.5, 145.0, y, "Unit");
-    write_text(&layer, &font_bold, 9.5, 172.0, y, "Total");
-    layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
-    y -= 4.0;
-    draw_divider(&layer, left_x, y, 192.0);
-    y -= 6.0;
-
-    for line in &data.lines {
-        let description = line.description.clone();
-        write_text(&layer, &font, 9.0, left_x, y, &description);
-        write_text(&layer, &font, 9.0, 122.0, y, &line.quantity);
-        write_text(
-            &layer,
-            &font,
-            9.0,
-            145.0,
-            y,
-            &format!("{} {}", data.currency, line.unit_price),
-        );
-        write_text(
-            &layer,
-            &font,
-            9.0,
-            172.0,
-            y,
-            &format!("{} {}", data.currency, line.total),
-        );
-        y -= line_height;
-    }
-
-    y -= 4.0;
-    draw_divider(&layer, left_x, y, 192.0);
-    y -= 7.0;
-    write_text(
-        &layer,
-        &font,
-        10.0,
-        130.0,
-        y,
-        &format!("Subtotal: {} {}", data.currency, data.subtotal),
-    );
-    y -= line_height;
-    write_text(
-        &layer,
-        &font,
-        10.0,
-        130.0,
-        y,
-        &format!("VAT: {} {}", data.currency, data.tax_total),
-    );
-    y -= line_height;
-    write_text(
-        &layer,
-        &font_bold,
-        12.0,
-        130.0,
-        y,
-        &format!("Total: {} {}", data.currency, data.total),
-    );
-
-    let mut writer = BufWriter::new(File::create(output_file)?);
-    doc.save(&mut writer).context("write PDF")?;
-    Ok(())
-}
-
 fn parse_address(party: Option<&Node<'_, '_>>) -> Address {
     let Some(party) = party else {
-        return Address {
-            street: String::new(),
-            city: String::new(),
-            postal: String::new(),
-        };
+        return Address::default();
     };
 
     let address_node = find_descendant(*party, "PostalAddress");
-    Address {
-        street: address_node
+    let address_text = |name| {
+        address_node
             .as_ref()
-            .and_then(|node| find_text(node, "StreetName"))
-            .unwrap_or_default(),
-        city: address_node
-            .as_ref()
-            .and_then(|node| find_text(node, "CityName"))
-            .unwrap_or_default(),
-        postal: address_node
-            .as_ref()
-            .and_then(|node| find_text(node, "PostalZone"))
+            .and_then(|node| find_text(node, name))
+            .unwrap_or_default()
+    };
+    Address {
+        street: address_text("StreetName"),
+        city: address_text("CityName"),
+        postal: address_text("PostalZone"),
+        additional_street: address_text("AdditionalStreetName"),
+        building_number: address_text("BuildingNumber"),
+        country: address_node
+            .and_then(|node| find_descendant(node, "Country"))
+            .map(|country| {
+                let code = find_text(&country, "IdentificationCode").unwrap_or_default();
+                find_text_ns(&country, CBC_NS, "Name")
+                    .or_else(|| country_name(&code).map(str::to_string))
+                    .unwrap_or(code)
+            })
             .unwrap_or_default(),
     }
 }
 
+/// Short English label for the UN/ECE unit codes most common on invoices.
+fn unit_label(code: &str) -> Option<&'static str> {
+    let label = match code.to_ascii_uppercase().as_str() {
+        "C62" | "H87" | "EA" => "pcs",
+        "HUR" => "hrs",
+        "MIN" => "min",
+        "DAY" => "days",
+        "WEE" => "weeks",
+        "MON" => "months",
+        "ANN" => "years",
+        "KGM" => "kg",
+        "GRM" => "g",
+        "TNE" => "t",
+        "MTR" => "m",
+        "KMT" => "km",
+        "MTK" => "m2",
+        "MTQ" => "m3",
+        "LTR" => "l",
+        "KWH" => "kWh",
+        "SET" => "sets",
+        "PR" => "pairs",
+        "LS" => "lump sum",
+        _ => return None,
+    };
+    Some(label)
+}
+
+/// English name for the ISO 3166-1 alpha-2 codes most often seen on
+/// European e-invoices.
+fn country_name(code: &str) -> Option<&'static str> {
+    let name = match code.to_ascii_uppercase().as_str() {
+        "AT" => "Austria",
+        "BE" => "Belgium",
+        "BG" => "Bulgaria",
+        "CH" => "Switzerland",
+        "CY" => "Cyprus",
+        "CZ" => "Czechia",
+        "DE" => "Germany",
+        "DK" => "Denmark",
+        "EE" => "Estonia",
+        "ES" => "Spain",
+        "FI" => "Finland",
+        "FR" => "France",
+        "GB" => "United Kingdom",
+        "GR" => "Greece",
+        "HR" => "Croatia",
+        "HU" => "Hungary",
+        "IE" => "Ireland",
+        "IS" => "Iceland",
+        "IT" => "Italy",
+        "LI" => "Liechtenstein",
+        "LT" => "Lithuania",
+        "LU" => "Luxembourg",
+        "LV" => "Latvia",
+        "MT" => "Malta",
+        "NL" => "Netherlands",
+        "NO" => "Norway",
+        "PL" => "Poland",
+        "PT" => "Portugal",
+        "RO" => "Romania",
+        "SE" => "Sweden",
+        "SI" => "Slovenia",
+        "SK" => "Slovakia",
+        "US" => "United States",
+        _ => return None,
+    };
+    Some(name)
+}
+
+fn parse_allowance_charge(node: Node<'_, '_>) -> AllowanceCharge {
+    let child_text = |name| find_child_text(&node, name).unwrap_or_default();
+    AllowanceCharge {
+        is_charge: child_text("ChargeIndicator").eq_ignore_ascii_case("true"),
+        amount: child_text("Amount"),
+        reason: child_text("AllowanceChargeReason"),
+    }
+}
+
+fn parse_tax_subtotal(node: Node<'_, '_>) -> TaxSubtotal {
+    let category = find_child(node, "TaxCategory");
+    let category_text =
+        |name| category.and_then(|node| find_child_text(&node, name)).unwrap_or_default();
+    TaxSubtotal {
+        taxable_amount: find_child_text(&node, "TaxableAmount").unwrap_or_default(),
+        tax_amount: find_child_text(&node, "TaxAmount").unwrap_or_default(),
+        percent: category_text("Percent"),
+        category: category_text("ID"),
+        exemption_reason: category_text("TaxExemptionReason"),
+        exemption_reason_code: category_text("TaxExemptionReasonCode"),
+    }
+}
+
+/// Returns the payee IBAN and BIC, preferring the first credit-transfer
+/// `PaymentMeans` (codes 30 and 58) over any other.
+fn parse_payment_account(root: Node<'_, '_>) -> (String, String) {
+    let means: Vec<Node> = find_children(root, "PaymentMeans").collect();
+    let credit_transfer = means.iter().find(|node| {
+        matches!(
+            find_child_text(node, "PaymentMeansCode").as_deref(),
+            Some("30" | "58")
+        )
+    });
+    let Some(account) = credit_transfer
+        .or(means.first())
+        .and_then(|node| find_child(*node, "PayeeFinancialAccount"))
+    else {
+        return (String::new(), String::new());
+    };
+
+    let iban = find_child_text(&account, "ID").unwrap_or_default();
+    validate::warn_on_invalid_iban(&iban);
+    let bic = find_child(account, "FinancialInstitutionBranch")
+        .and_then(|branch| find_child_text(&branch, "ID"))
+        .unwrap_or_default();
+    (iban, bic)
+}
+
+/// The company name: `PartyName/Name`, else the legal `RegistrationName`, and
+/// only then any `Name`, which may belong to a contact person or a country.
+fn parse_party_name(party: Option<Node<'_, '_>>) -> String {
+    let Some(party) = party else {
+        return String::new();
+    };
+    find_child(party, "PartyName")
+        .and_then(|node| find_child_text_ns(&node, CBC_NS, "Name"))
+        .or_else(|| {
+            find_child(party, "PartyLegalEntity")
+                .and_then(|node| find_child_text_ns(&node, CBC_NS, "RegistrationName"))
+        })
+        .or_else(|| find_child_text_ns(&party, CBC_NS, "Name"))
+        .or_else(|| find_text_ns(&party, CBC_NS, "Name"))
+        .unwrap_or_default()
+}
+
+fn parse_legal_name(party: Node<'_, '_>) -> String {
+    find_child(party, "PartyLegalEntity")
+        .and_then(|node| find_child_text_ns(&node, CBC_NS, "RegistrationName"))
+        .unwrap_or_default()
+}
+
+fn parse_contact(party: Node<'_, '_>) -> Option<Contact> {
+    let contact = find_child(party, "Contact")?;
+    let text = |name| find_child_text_ns(&contact, CBC_NS, name).unwrap_or_default();
+    let contact = Contact {
+        name: text("Name"),
+        telephone: text("Telephone"),
+        email: text("ElectronicMail"),
+    };
+    (contact != Contact::default()).then_some(contact)
+}
+
+fn parse_endpoint(party: Node<'_, '_>) -> Option<Endpoint> {
+    let node = party
+        .children()
+        .find(|child| is_element_ns(child, CBC_NS, "EndpointID"))?;
+    let value = node.text().map(str::trim).unwrap_or_default();
+    if value.is_empty() {
+        return None;
+    }
+    Some(Endpoint {
+        value: value.to_string(),
+        scheme: node.attribute("schemeID").unwrap_or_default().to_string(),
+    })
+}
+
+fn parse_party_vat(party: Option<&Node<'_, '_>>) -> String {
+    let Some(party) = party else {
+        return String::new();
+    };
+
+    // CompanyID appears under both PartyTaxScheme (VAT number) and
+    // PartyLegalEntity (registration number); prefer the tax scheme.
+    ["PartyTaxScheme", "PartyLegalEntity"]
+        .iter()
+        .filter_map(|scope| find_descendant(*party, scope))
+        .find_map(|node| find_text_ns(&node, CBC_NS, "CompanyID"))
+        .unwrap_or_default()
+}
+
+/// The registered name when it is worth printing under the display name.
+pub(crate) fn distinct_legal_name<'a>(name: &str, legal_name: &'a str) -> Option<&'a str> {
+    (!legal_name.is_empty() && legal_name != name).then_some(legal_name)
+}
+
+/// The non-empty lines of a party's postal address, in printing order.
+pub(crate) fn address_lines(address: &Address) -> Vec<String> {
+    let postal_city = format!("{} {}", address.postal, address.city);
+    [
+        address.street_line(),
+        address.additional_street.clone(),
+        postal_city.trim().to_string(),
+        address.country.clone(),
+    ]
+    .into_iter()
+    .filter(|line| !line.is_empty())
+    .collect()
+}
+
+/// A line discount or surcharge as printed under the item, e.g.
+/// "-2.00 discount"; `amount` is already formatted.
+pub(crate) fn adjustment_label(entry: &AllowanceCharge, amount: &str) -> String {
+    let (sign, fallback) = if entry.is_charge {
+        ("+", "charge")
+    } else {
+        ("-", "discount")
+    };
+    let reason = if entry.reason.is_empty() {
+        fallback
+    } else {
+        &entry.reason
+    };
+    format!("{sign}{amount} {reason}")
+}
+
+/// Adds decimal amounts, keeping the widest fractional precision seen. A single
+/// amount is returned verbatim; unparsable input falls back to the first value.
+/// Grouped amounts such as `1.234,56` are read by their last separator.
+fn sum_amounts(amounts: &[String]) -> String {
+    match amounts {
+        [] => String::new(),
+        [single] => single.clone(),
+        _ => {
+            let mut sum = 0.0;
+            let mut decimals = 0;
+            for amount in amounts {
+                let Some((value, fraction)) = format::parse_grouped_amount(amount) else {
+                    return amounts[0].clone();
+                };
+                sum += value;
+                decimals = decimals.max(fraction);
+            }
+            format!("{sum:.decimals$}")
+        }
+    }
+}
+
+fn find_child<'a>(node: Node<'a, 'a>, name: &str) -> Option<Node<'a, 'a>> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn find_children<'a>(
+    node: Node<'a, 'a>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'a>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
 fn find_descendant<'a>(node: Node<'a, 'a>, name: &str) -> Option<Node<'a, 'a>> {
     node.descendants()
         .find(|child| child.is_element() && child.tag_name().name() == name)
 }
 
+/// `(element, currencyID)` of each child of `parent` named in `names` that
+/// carries the attribute, in the order of `names`.
+pub(crate) fn amount_currencies(parent: Option<Node<'_, '_>>, names: &[&str]) -> Vec<(String, String)> {
+    let Some(parent) = parent else {
+        return Vec::new();
+    };
+    names
+        .iter()
+        .filter_map(|name| {
+            let currency = find_child(parent, name)?.attribute("currencyID")?;
+            Some((name.to_string(), currency.trim().to_string()))
+        })
+        .collect()
+}
+
 fn find_text(node: &Node<'_, '_>, name: &str) -> Option<String> {
     find_descendant(*node, name)
         .and_then(|child| child.text())
         .map(|text| text.trim().to_string())
 }
 
-fn write_text(
-    layer: &PdfLayerReference,
-    font: &IndirectFontRef,
-    size: f64,
-    x: f64,
-    y: f64,
-    text: &str,
-) {
-    layer.use_text(text, size as f32, Mm(x as f32), Mm(y as f32), font);
-}
-
-fn draw_divider(layer: &PdfLayerReference, x1: f64, y: f64, x2: f64) {
-    layer.set_outline_thickness(0.3);
-    layer.set_outline_color(Color::Rgb(Rgb::new(0.75, 0.75, 0.75, None)));
-    let line = Line {
-        points: vec![
-            (Point::new(Mm(x1 as f32), Mm(y as f32)), false),
-            (Point::new(Mm(x2 as f32), Mm(y as f32)), false),
-        ],
-        is_closed: false,
-    };
-    layer.add_line(line);
-    layer.set_outline_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
-    layer.set_outline_thickness(1.0);
+fn find_child_text(node: &Node<'_, '_>, name: &str) -> Option<String> {
+    find_child(*node, name)
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+}
+
+/// Whether `node` is the element `name` in namespace `ns`. Elements without
+/// any namespace also match, so documents that omit the UBL namespaces still
+/// parse; only elements from a different namespace are rejected.
+fn is_element_ns(node: &Node<'_, '_>, ns: &str, name: &str) -> bool {
+    let tag = node.tag_name();
+    node.is_element() && tag.name() == name && tag.namespace().is_none_or(|uri| uri == ns)
+}
+
+/// Like [`find_text`], but skips same-named elements from other namespaces,
+/// such as extension content reusing `ID` or `Name`.
+fn find_text_ns(node: &Node<'_, '_>, ns: &str, name: &str) -> Option<String> {
+    node.descendants()
+        .find(|child| is_element_ns(child, ns, name))
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+}
+
+/// Like [`find_child_text`], but namespace-aware as [`find_text_ns`].
+fn find_child_text_ns(node: &Node<'_, '_>, ns: &str, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| is_element_ns(child, ns, name))
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    const SAMPLE_XML: &str = r#"
+    pub(crate) const SAMPLE_XML: &str = r#"
 <Invoice xmlns="urn:oasis:names:specification:ubl:schema:xsd:Invoice-2"
  xmlns:cac="urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2"
  xmlns:cbc="urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2">
@@ -519,4 +1105,814 @@ mod tests {
         assert_eq!(embedded.filename.as_deref(), Some("orig.pdf"));
         assert_eq!(embedded.bytes, b"hello");
     }
+
+    #[test]
+    fn extracts_all_embedded_documents() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:AccountingSupplierParty>",
+            r#"  <cac:AdditionalDocumentReference>
+    <cbc:ID>timesheet</cbc:ID>
+    <cac:Attachment>
+      <cbc:EmbeddedDocumentBinaryObject mimeCode="text/csv" filename="hours.csv">YSxi</cbc:EmbeddedDocumentBinaryObject>
+    </cac:Attachment>
+  </cac:AdditionalDocumentReference>
+  <cac:AccountingSupplierParty>"#,
+        );
+        let documents = extract_embedded_documents(&xml).expect("extract embedded");
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0].mime_code, "text/csv");
+        assert_eq!(documents[0].filename.as_deref(), Some("hours.csv"));
+        assert_eq!(documents[0].bytes, b"a,b");
+        assert_eq!(documents[1].mime_code, "application/pdf");
+
+        let embedded = extract_embedded_pdf(&xml)
+            .expect("extract embedded")
+            .expect("embedded pdf present");
+        assert_eq!(embedded.filename.as_deref(), Some("orig.pdf"));
+    }
+
+    #[test]
+    fn extracts_external_reference_uris() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:AccountingSupplierParty>",
+            r#"  <cac:AdditionalDocumentReference>
+    <cbc:ID>timesheet</cbc:ID>
+    <cac:Attachment>
+      <cac:ExternalReference>
+        <cbc:URI>https://example.com/timesheets/2024-01.pdf</cbc:URI>
+      </cac:ExternalReference>
+    </cac:Attachment>
+  </cac:AdditionalDocumentReference>
+  <cac:AccountingSupplierParty>"#,
+        );
+        let uris = extract_external_references(&xml).expect("extract references");
+        assert_eq!(uris, ["https://example.com/timesheets/2024-01.pdf"]);
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.external_references, uris);
+
+        assert!(extract_external_references(SAMPLE_XML).expect("extract references").is_empty());
+    }
+
+    #[test]
+    fn extracts_wrapped_embedded_pdf() {
+        let xml = SAMPLE_XML.replace(
+            ">aGVsbG8=<",
+            ">\n        aGVsbG8g\r\n        d29ybGQ=\n\t<",
+        );
+        let embedded = extract_embedded_pdf(&xml)
+            .expect("extract embedded")
+            .expect("embedded pdf present");
+        assert_eq!(embedded.bytes, b"hello world");
+    }
+
+    #[test]
+    fn prefers_tax_scheme_company_id_for_vat() {
+        let xml = SAMPLE_XML
+            .replace(
+                "      <cbc:Name>Supplier Inc</cbc:Name>\n",
+                "      <cbc:Name>Supplier Inc</cbc:Name>\n      <cac:PartyLegalEntity>\n        <cbc:CompanyID>REG-001</cbc:CompanyID>\n      </cac:PartyLegalEntity>\n",
+            )
+            .replace(
+                "      <cac:PartyTaxScheme>\n        <cbc:CompanyID>VAT999</cbc:CompanyID>\n      </cac:PartyTaxScheme>\n",
+                "      <cac:PartyLegalEntity>\n        <cbc:CompanyID>REG-999</cbc:CompanyID>\n      </cac:PartyLegalEntity>\n",
+            );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.supplier_vat, "VAT123");
+        assert_eq!(data.customer_vat, "REG-999");
+    }
+
+    #[test]
+    fn sums_document_level_tax_totals() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:TaxTotal>\n    <cbc:TaxAmount>2.00</cbc:TaxAmount>\n  </cac:TaxTotal>\n",
+            r#"  <cac:TaxTotal>
+    <cac:TaxSubtotal>
+      <cbc:TaxableAmount>8.00</cbc:TaxableAmount>
+      <cbc:TaxAmount>1.68</cbc:TaxAmount>
+    </cac:TaxSubtotal>
+    <cac:TaxSubtotal>
+      <cbc:TaxableAmount>2.00</cbc:TaxableAmount>
+      <cbc:TaxAmount>0.12</cbc:TaxAmount>
+    </cac:TaxSubtotal>
+    <cbc:TaxAmount>1.80</cbc:TaxAmount>
+  </cac:TaxTotal>
+  <cac:TaxTotal>
+    <cbc:TaxAmount>0.20</cbc:TaxAmount>
+  </cac:TaxTotal>
+"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.tax_total, "2.00");
+    }
+
+    #[test]
+    fn sums_grouped_comma_amounts() {
+        let amounts = ["1.234,56".to_string(), "1.000,44".to_string()];
+        assert_eq!(sum_amounts(&amounts), "2235.00");
+    }
+
+    #[test]
+    fn parses_tax_breakdown_per_rate() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:TaxTotal>\n    <cbc:TaxAmount>2.00</cbc:TaxAmount>\n  </cac:TaxTotal>\n",
+            r#"  <cac:TaxTotal>
+    <cbc:TaxAmount>1.80</cbc:TaxAmount>
+    <cac:TaxSubtotal>
+      <cbc:TaxableAmount>8.00</cbc:TaxableAmount>
+      <cbc:TaxAmount>1.68</cbc:TaxAmount>
+      <cac:TaxCategory>
+        <cbc:ID>S</cbc:ID>
+        <cbc:Percent>21</cbc:Percent>
+      </cac:TaxCategory>
+    </cac:TaxSubtotal>
+    <cac:TaxSubtotal>
+      <cbc:TaxableAmount>2.00</cbc:TaxableAmount>
+      <cbc:TaxAmount>0.12</cbc:TaxAmount>
+      <cac:TaxCategory>
+        <cbc:ID>AA</cbc:ID>
+        <cbc:Percent>6</cbc:Percent>
+      </cac:TaxCategory>
+    </cac:TaxSubtotal>
+  </cac:TaxTotal>
+"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.tax_breakdown.len(), 2);
+        assert_eq!(data.tax_breakdown[0].taxable_amount, "8.00");
+        assert_eq!(data.tax_breakdown[0].tax_amount, "1.68");
+        assert_eq!(data.tax_breakdown[0].percent, "21");
+        assert_eq!(data.tax_breakdown[0].category, "S");
+        assert_eq!(data.tax_breakdown[1].percent, "6");
+        assert_eq!(data.tax_breakdown[1].category, "AA");
+        #[cfg(feature = "pdf")]
+        create_invoice_pdf_bytes(&data).expect("render breakdown");
+    }
+
+    #[test]
+    fn parses_tax_exemption_reasons_per_category() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:TaxTotal>\n    <cbc:TaxAmount>2.00</cbc:TaxAmount>\n  </cac:TaxTotal>\n",
+            r#"  <cac:TaxTotal>
+    <cbc:TaxAmount>0.00</cbc:TaxAmount>
+    <cac:TaxSubtotal>
+      <cbc:TaxableAmount>6.00</cbc:TaxableAmount>
+      <cbc:TaxAmount>0.00</cbc:TaxAmount>
+      <cac:TaxCategory>
+        <cbc:ID>AE</cbc:ID>
+        <cbc:Percent>0</cbc:Percent>
+        <cbc:TaxExemptionReasonCode>VATEX-EU-AE</cbc:TaxExemptionReasonCode>
+        <cbc:TaxExemptionReason>Reverse charge, Art. 196</cbc:TaxExemptionReason>
+      </cac:TaxCategory>
+    </cac:TaxSubtotal>
+    <cac:TaxSubtotal>
+      <cbc:TaxableAmount>4.00</cbc:TaxableAmount>
+      <cbc:TaxAmount>0.00</cbc:TaxAmount>
+      <cac:TaxCategory>
+        <cbc:ID>K</cbc:ID>
+        <cbc:Percent>0</cbc:Percent>
+        <cbc:TaxExemptionReasonCode>VATEX-EU-IC</cbc:TaxExemptionReasonCode>
+      </cac:TaxCategory>
+    </cac:TaxSubtotal>
+  </cac:TaxTotal>
+"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let [reverse_charge, intra_community] = &data.tax_breakdown[..] else {
+            panic!("expected two tax subtotals");
+        };
+        assert_eq!(reverse_charge.exemption_reason, "Reverse charge, Art. 196");
+        assert_eq!(reverse_charge.exemption_reason_code, "VATEX-EU-AE");
+        assert_eq!(reverse_charge.exemption(), "Reverse charge, Art. 196");
+        assert!(intra_community.exemption_reason.is_empty());
+        assert_eq!(intra_community.exemption(), "VATEX-EU-IC");
+        #[cfg(feature = "pdf")]
+        create_invoice_pdf_bytes(&data).expect("render exemptions");
+    }
+
+    #[test]
+    fn parses_document_level_allowances_and_charges() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:LegalMonetaryTotal>",
+            r#"  <cac:AllowanceCharge>
+    <cbc:ChargeIndicator>false</cbc:ChargeIndicator>
+    <cbc:AllowanceChargeReason>Loyalty discount</cbc:AllowanceChargeReason>
+    <cbc:Amount currencyID="EUR">1.00</cbc:Amount>
+  </cac:AllowanceCharge>
+  <cac:AllowanceCharge>
+    <cbc:ChargeIndicator>true</cbc:ChargeIndicator>
+    <cbc:AllowanceChargeReason>Freight</cbc:AllowanceChargeReason>
+    <cbc:Amount currencyID="EUR">0.50</cbc:Amount>
+  </cac:AllowanceCharge>
+  <cac:LegalMonetaryTotal>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.allowances_charges.len(), 2);
+        let discount = &data.allowances_charges[0];
+        assert!(!discount.is_charge);
+        assert_eq!(discount.amount, "1.00");
+        assert_eq!(discount.reason, "Loyalty discount");
+        let charge = &data.allowances_charges[1];
+        assert!(charge.is_charge);
+        assert_eq!(charge.amount, "0.50");
+        assert_eq!(charge.reason, "Freight");
+    }
+
+    #[test]
+    fn parse_invoice_dispatches_on_root_element() {
+        let data = parse_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(data.invoice_number, "INV-1");
+
+        let err = parse_invoice("<Order><ID>1</ID></Order>").expect_err("unknown root");
+        assert!(matches!(err, InvoiceError::UnsupportedDocument(ref root) if root == "Order"));
+    }
+
+    #[test]
+    fn xml_errors_name_the_line_and_column() {
+        let xml = "<Invoice>\n  <ID>1</ID>\n  <Note></Nope>\n</Invoice>";
+        let err = parse_ubl_invoice(xml).expect_err("malformed XML");
+        assert!(matches!(err, InvoiceError::Xml(_)));
+        let message = err.to_string();
+        assert!(message.starts_with("XML parse error at 3:"), "{message}");
+
+        // Truncated documents have no position to report.
+        let err = parse_invoice("<Invoice>").expect_err("malformed XML");
+        assert_eq!(
+            err.to_string(),
+            "XML parse error: the root node was opened but never closed"
+        );
+    }
+
+    #[test]
+    fn blank_input_is_an_empty_document() {
+        for xml in ["", " \n\t\r\n "] {
+            let err = parse_invoice(xml).expect_err("blank input");
+            assert!(matches!(err, InvoiceError::EmptyDocument));
+            assert_eq!(err.to_string(), "empty document");
+            let err = parse_ubl_invoice(xml).expect_err("blank input");
+            assert!(matches!(err, InvoiceError::EmptyDocument));
+        }
+    }
+
+    #[test]
+    fn reports_error_kinds() {
+        let err = parse_invoice("<Invoice>").expect_err("malformed XML");
+        assert!(matches!(err, InvoiceError::Xml(_)));
+
+        let xml = SAMPLE_XML.replace("aGVsbG8=", "not*base64");
+        let err = extract_embedded_pdf(&xml).expect_err("corrupt attachment");
+        assert!(matches!(err, InvoiceError::Base64(_)));
+    }
+
+    #[test]
+    fn parses_credit_transfer_account() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:LegalMonetaryTotal>",
+            r#"  <cac:PaymentMeans>
+    <cbc:PaymentMeansCode>48</cbc:PaymentMeansCode>
+    <cac:PayeeFinancialAccount>
+      <cbc:ID>CARD-ACCOUNT</cbc:ID>
+    </cac:PayeeFinancialAccount>
+  </cac:PaymentMeans>
+  <cac:PaymentMeans>
+    <cbc:PaymentMeansCode>58</cbc:PaymentMeansCode>
+    <cac:PayeeFinancialAccount>
+      <cbc:ID>FR7630006000011234567890189</cbc:ID>
+      <cac:FinancialInstitutionBranch>
+        <cbc:ID>AGRIFRPP</cbc:ID>
+      </cac:FinancialInstitutionBranch>
+    </cac:PayeeFinancialAccount>
+  </cac:PaymentMeans>
+  <cac:LegalMonetaryTotal>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.payment_iban, "FR7630006000011234567890189");
+        assert_eq!(data.payment_bic, "AGRIFRPP");
+    }
+
+    #[test]
+    fn parses_split_street_and_building_number() {
+        let xml = SAMPLE_XML.replace(
+            "<cbc:StreetName>Main</cbc:StreetName>",
+            r#"<cbc:StreetName>Keizersgracht</cbc:StreetName>
+        <cbc:AdditionalStreetName>Unit 4</cbc:AdditionalStreetName>
+        <cbc:BuildingNumber>12</cbc:BuildingNumber>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let address = &data.supplier_address;
+        assert_eq!(address.street, "Keizersgracht");
+        assert_eq!(address.additional_street, "Unit 4");
+        assert_eq!(address.building_number, "12");
+        assert_eq!(address.street_line(), "Keizersgracht 12");
+        assert_eq!(data.customer_address.street_line(), "Rue 1");
+    }
+
+    #[test]
+    fn parses_country_name_or_maps_iso_code() {
+        let xml = SAMPLE_XML
+            .replace(
+                "<cbc:PostalZone>75001</cbc:PostalZone>",
+                r#"<cbc:PostalZone>75001</cbc:PostalZone>
+        <cac:Country><cbc:IdentificationCode>FR</cbc:IdentificationCode></cac:Country>"#,
+            )
+            .replace(
+                "<cbc:StreetName>Rue 1</cbc:StreetName>",
+                r#"<cbc:StreetName>Rue 1</cbc:StreetName>
+        <cac:Country><cbc:IdentificationCode>XK</cbc:IdentificationCode><cbc:Name>Kosovo</cbc:Name></cac:Country>"#,
+            );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.supplier_address.country, "France");
+        assert_eq!(data.customer_address.country, "Kosovo");
+        let plain = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(plain.supplier_address.country, "");
+    }
+
+    #[test]
+    fn parses_credit_note_lines() {
+        let xml = r#"<CreditNote xmlns="urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2" xmlns:cac="urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2" xmlns:cbc="urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2">
+  <cbc:ID>CN-7</cbc:ID>
+  <cbc:IssueDate>2024-02-01</cbc:IssueDate>
+  <cbc:DocumentCurrencyCode>EUR</cbc:DocumentCurrencyCode>
+  <cac:LegalMonetaryTotal>
+    <cbc:TaxExclusiveAmount currencyID="EUR">5.00</cbc:TaxExclusiveAmount>
+    <cbc:TaxInclusiveAmount currencyID="EUR">6.00</cbc:TaxInclusiveAmount>
+  </cac:LegalMonetaryTotal>
+  <cac:CreditNoteLine>
+    <cbc:ID>1</cbc:ID>
+    <cbc:CreditedQuantity unitCode="EA">1</cbc:CreditedQuantity>
+    <cbc:LineExtensionAmount currencyID="EUR">5.00</cbc:LineExtensionAmount>
+    <cac:Item><cbc:Description>Returned widget</cbc:Description></cac:Item>
+    <cac:Price><cbc:PriceAmount currencyID="EUR">5.00</cbc:PriceAmount></cac:Price>
+  </cac:CreditNoteLine>
+</CreditNote>"#;
+        let data = parse_invoice(xml).expect("parse credit note");
+        assert_eq!(data.document_type, "CreditNote");
+        assert_eq!(data.invoice_number, "CN-7");
+        assert_eq!(data.lines.len(), 1);
+        assert_eq!(data.lines[0].description, "Returned widget");
+        assert_eq!(data.lines[0].quantity, "1");
+        assert_eq!(data.lines[0].total, "5.00");
+
+        let invoice = parse_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(invoice.document_type, "Invoice");
+    }
+
+    #[test]
+    fn parses_the_preceding_invoice_reference() {
+        let xml = r#"<CreditNote xmlns="urn:oasis:names:specification:ubl:schema:xsd:CreditNote-2" xmlns:cac="urn:oasis:names:specification:ubl:schema:xsd:CommonAggregateComponents-2" xmlns:cbc="urn:oasis:names:specification:ubl:schema:xsd:CommonBasicComponents-2">
+  <cbc:ID>CN-8</cbc:ID>
+  <cbc:IssueDate>2024-02-01</cbc:IssueDate>
+  <cbc:DocumentCurrencyCode>EUR</cbc:DocumentCurrencyCode>
+  <cac:BillingReference>
+    <cac:InvoiceDocumentReference>
+      <cbc:ID>INV-2024-042</cbc:ID>
+      <cbc:IssueDate>2024-01-15</cbc:IssueDate>
+    </cac:InvoiceDocumentReference>
+  </cac:BillingReference>
+</CreditNote>"#;
+        let data = parse_invoice(xml).expect("parse credit note");
+        assert_eq!(data.preceding_invoice_reference, "INV-2024-042");
+
+        let invoice = parse_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(invoice.preceding_invoice_reference.is_empty());
+    }
+
+    #[test]
+    fn parses_buyer_and_order_references() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:AccountingSupplierParty>",
+            r#"  <cbc:BuyerReference>04011000-12345-67</cbc:BuyerReference>
+  <cac:OrderReference>
+    <cbc:ID>PO-2024-118</cbc:ID>
+  </cac:OrderReference>
+  <cac:AccountingSupplierParty>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.buyer_reference, "04011000-12345-67");
+        assert_eq!(data.order_reference, "PO-2024-118");
+        assert_eq!(data.invoice_number, "INV-1");
+    }
+
+    #[test]
+    fn collects_document_level_notes_only() {
+        let xml = SAMPLE_XML
+            .replace(
+                "  <cac:AccountingSupplierParty>",
+                r#"  <cbc:Note>Payment within 30 days.</cbc:Note>
+  <cbc:Note>Thank you for your business!</cbc:Note>
+  <cac:AccountingSupplierParty>"#,
+            )
+            .replace(
+                "<cac:Item>",
+                "<cbc:Note>Line-level remark</cbc:Note><cac:Item>",
+            );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(
+            data.notes,
+            ["Payment within 30 days.", "Thank you for your business!"]
+        );
+    }
+
+    #[test]
+    fn parses_payment_terms_note() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:AccountingSupplierParty>",
+            r#"  <cac:PaymentTerms>
+    <cbc:Note>Net 30, 2% discount if paid within 10 days</cbc:Note>
+  </cac:PaymentTerms>
+  <cac:AccountingSupplierParty>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(
+            data.payment_terms,
+            "Net 30, 2% discount if paid within 10 days"
+        );
+        assert!(data.notes.is_empty());
+    }
+
+    #[test]
+    fn parses_supplier_contact() {
+        let with_contact = |contact: &str| {
+            SAMPLE_XML.replacen(
+                "      </cac:PostalAddress>\n    </cac:Party>",
+                &format!("      </cac:PostalAddress>\n{contact}\n    </cac:Party>"),
+                1,
+            )
+        };
+        let xml = with_contact(
+            r#"      <cac:Contact>
+        <cbc:Name>Jane Doe</cbc:Name>
+        <cbc:Telephone>+33 1 23 45 67 89</cbc:Telephone>
+        <cbc:ElectronicMail>billing@supplier.example</cbc:ElectronicMail>
+      </cac:Contact>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let contact = data.supplier_contact.expect("supplier contact");
+        assert_eq!(contact.name, "Jane Doe");
+        assert_eq!(contact.telephone, "+33 1 23 45 67 89");
+        assert_eq!(contact.email, "billing@supplier.example");
+        assert_eq!(data.supplier_name, "Supplier Inc");
+
+        let xml = with_contact(
+            "      <cac:Contact><cbc:ElectronicMail>ap@supplier.example</cbc:ElectronicMail></cac:Contact>",
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let contact = data.supplier_contact.expect("supplier contact");
+        assert_eq!(contact.summary(), "ap@supplier.example");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(data.supplier_contact, None);
+    }
+
+    #[test]
+    fn parses_party_endpoints_with_scheme() {
+        let xml = SAMPLE_XML
+            .replacen(
+                "<cbc:Name>Supplier Inc</cbc:Name>",
+                r#"<cbc:EndpointID schemeID="0088">7300010000001</cbc:EndpointID>
+      <cbc:Name>Supplier Inc</cbc:Name>"#,
+                1,
+            )
+            .replacen(
+                "<cbc:Name>Customer LLC</cbc:Name>",
+                r#"<cbc:EndpointID schemeID="9930">DE123456789</cbc:EndpointID>
+      <cbc:Name>Customer LLC</cbc:Name>"#,
+                1,
+            );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let supplier = data.supplier_endpoint.expect("supplier endpoint");
+        assert_eq!(supplier.value, "7300010000001");
+        assert_eq!(supplier.scheme, "0088");
+        let customer = data.customer_endpoint.expect("customer endpoint");
+        assert_eq!(customer.value, "DE123456789");
+        assert_eq!(customer.scheme, "9930");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(data.supplier_endpoint, None);
+    }
+
+    #[test]
+    fn debug_field_map_lists_leaf_elements() {
+        let fields = debug_field_map(SAMPLE_XML).expect("field map");
+        assert_eq!(fields["ID"], "INV-1");
+        assert_eq!(fields["Name"], "Supplier Inc");
+        assert_eq!(fields["Name#2"], "Customer LLC");
+        assert_eq!(fields["StreetName"], "Main");
+        assert!(!fields.contains_key("AccountingSupplierParty"));
+        assert!(debug_field_map("<Invoice>").is_err());
+    }
+
+    #[test]
+    fn parses_legal_names_apart_from_trading_names() {
+        let xml = SAMPLE_XML.replacen(
+            "      <cbc:Name>Supplier Inc</cbc:Name>",
+            r#"      <cac:PartyName>
+        <cbc:Name>Acme Widgets</cbc:Name>
+      </cac:PartyName>
+      <cac:PartyLegalEntity>
+        <cbc:RegistrationName>Acme Holding SAS</cbc:RegistrationName>
+      </cac:PartyLegalEntity>"#,
+            1,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.supplier_name, "Acme Widgets");
+        assert_eq!(data.supplier_legal_name, "Acme Holding SAS");
+        assert!(data.customer_legal_name.is_empty());
+    }
+
+    #[test]
+    fn prefers_party_name_over_earlier_contact_name() {
+        let xml = SAMPLE_XML.replacen(
+            "      <cbc:Name>Supplier Inc</cbc:Name>",
+            r#"      <cac:Contact>
+        <cbc:Name>Jane Doe</cbc:Name>
+      </cac:Contact>
+      <cac:PartyName>
+        <cbc:Name>Supplier Inc</cbc:Name>
+      </cac:PartyName>"#,
+            1,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.supplier_name, "Supplier Inc");
+        assert_eq!(data.supplier_contact.expect("contact").name, "Jane Doe");
+
+        let xml = SAMPLE_XML.replacen(
+            "      <cbc:Name>Customer LLC</cbc:Name>",
+            r#"      <cac:Contact>
+        <cbc:Name>John Roe</cbc:Name>
+      </cac:Contact>
+      <cac:PartyLegalEntity>
+        <cbc:RegistrationName>Customer LLC</cbc:RegistrationName>
+      </cac:PartyLegalEntity>"#,
+            1,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.customer_name, "Customer LLC");
+    }
+
+    #[test]
+    fn parses_quantity_unit_codes() {
+        let xml = SAMPLE_XML.replace(
+            "<cbc:InvoicedQuantity>1</cbc:InvoicedQuantity>",
+            r#"<cbc:InvoicedQuantity unitCode="HUR">3</cbc:InvoicedQuantity>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let line = &data.lines[0];
+        assert_eq!(line.quantity, "3");
+        assert_eq!(line.unit_code, "HUR");
+        assert_eq!(line.quantity_with_unit(), "3 hrs");
+
+        let line = InvoiceLine {
+            unit_code: "XBX".to_string(),
+            ..line.clone()
+        };
+        assert_eq!(line.quantity_with_unit(), "3 XBX");
+    }
+
+    #[test]
+    fn parses_seller_and_buyer_item_ids() {
+        let xml = SAMPLE_XML.replace(
+            "      <cbc:Description>Widget</cbc:Description>\n",
+            "      <cbc:Description>Widget</cbc:Description>
+      <cac:BuyersItemIdentification><cbc:ID>B-77</cbc:ID></cac:BuyersItemIdentification>
+      <cac:SellersItemIdentification><cbc:ID>SKU-123</cbc:ID></cac:SellersItemIdentification>\n",
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let line = &data.lines[0];
+        assert_eq!(line.seller_item_id, "SKU-123");
+        assert_eq!(line.buyer_item_id, "B-77");
+        assert_eq!(line.with_seller_item_id(&line.description), "[SKU-123] Widget");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(data.lines[0].with_seller_item_id("Widget"), "Widget");
+    }
+
+    #[test]
+    fn parses_the_order_line_reference() {
+        let xml = SAMPLE_XML.replace(
+            "    <cbc:LineExtensionAmount>10.00</cbc:LineExtensionAmount>\n",
+            "    <cbc:LineExtensionAmount>10.00</cbc:LineExtensionAmount>
+    <cac:OrderLineReference><cbc:LineID>7</cbc:LineID></cac:OrderLineReference>\n",
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.lines[0].order_line_id, "7");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.lines[0].order_line_id.is_empty());
+    }
+
+    #[test]
+    fn parses_line_level_allowances() {
+        let xml = SAMPLE_XML.replace(
+            "    <cbc:LineExtensionAmount>10.00</cbc:LineExtensionAmount>\n",
+            r#"    <cbc:LineExtensionAmount>10.00</cbc:LineExtensionAmount>
+    <cac:AllowanceCharge>
+      <cbc:ChargeIndicator>false</cbc:ChargeIndicator>
+      <cbc:AllowanceChargeReason>Loyalty discount</cbc:AllowanceChargeReason>
+      <cbc:Amount currencyID="EUR">2.00</cbc:Amount>
+    </cac:AllowanceCharge>
+"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        let line = &data.lines[0];
+        assert_eq!(line.allowances_charges.len(), 1);
+        assert!(!line.allowances_charges[0].is_charge);
+        assert_eq!(line.allowances_charges[0].amount, "2.00");
+        assert_eq!(line.allowances_charges[0].reason, "Loyalty discount");
+        assert!(data.allowances_charges.is_empty());
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.lines[0].allowances_charges.is_empty());
+    }
+
+    #[test]
+    fn parses_line_tax_category() {
+        let xml = SAMPLE_XML.replace(
+            "      <cbc:Description>Widget</cbc:Description>\n",
+            r#"      <cbc:Description>Widget</cbc:Description>
+      <cac:ClassifiedTaxCategory>
+        <cbc:ID>S</cbc:ID>
+        <cbc:Percent>19</cbc:Percent>
+        <cac:TaxScheme><cbc:ID>VAT</cbc:ID></cac:TaxScheme>
+      </cac:ClassifiedTaxCategory>
+"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.lines[0].tax_percent, "19");
+        assert_eq!(data.lines[0].tax_category, "S");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.lines[0].tax_percent.is_empty());
+    }
+
+    #[test]
+    fn parses_item_name_without_description() {
+        let xml = SAMPLE_XML.replace(
+            "<cbc:Description>Widget</cbc:Description>",
+            "<cbc:Name>Widget XL</cbc:Name>",
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.lines[0].name, "Widget XL");
+        assert!(data.lines[0].description.is_empty());
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.lines[0].name.is_empty());
+        assert_eq!(data.lines[0].description, "Widget");
+    }
+
+    #[test]
+    fn parses_invoice_period() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:AccountingSupplierParty>",
+            r#"  <cac:InvoicePeriod>
+    <cbc:StartDate>2024-01-01</cbc:StartDate>
+    <cbc:EndDate>2024-01-31</cbc:EndDate>
+  </cac:InvoicePeriod>
+  <cac:AccountingSupplierParty>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.period_start, "2024-01-01");
+        assert_eq!(data.period_end, "2024-01-31");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.period_start.is_empty() && data.period_end.is_empty());
+    }
+
+    #[test]
+    fn parses_rounding_and_payable_amounts() {
+        let xml = SAMPLE_XML.replace(
+            "    <cbc:TaxInclusiveAmount>12.00</cbc:TaxInclusiveAmount>\n",
+            "    <cbc:TaxInclusiveAmount>12.03</cbc:TaxInclusiveAmount>
+    <cbc:PayableRoundingAmount>-0.03</cbc:PayableRoundingAmount>
+    <cbc:PayableAmount>12.00</cbc:PayableAmount>\n",
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.total, "12.03");
+        assert_eq!(data.rounding_amount, "-0.03");
+        assert_eq!(data.payable_amount, "12.00");
+        assert_eq!(data.amount_due(), "12.00");
+        assert!(data.has_rounding());
+        assert!(data.has_separate_amount_due());
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(data.amount_due(), "12.00");
+        assert!(!data.has_rounding());
+        assert!(!data.has_separate_amount_due());
+    }
+
+    #[test]
+    fn parses_prepaid_amount_and_derives_the_amount_due() {
+        let xml = SAMPLE_XML.replace(
+            "    <cbc:TaxInclusiveAmount>12.00</cbc:TaxInclusiveAmount>\n",
+            "    <cbc:TaxInclusiveAmount>12.00</cbc:TaxInclusiveAmount>
+    <cbc:PrepaidAmount>5.00</cbc:PrepaidAmount>
+    <cbc:PayableAmount>7.00</cbc:PayableAmount>\n",
+        );
+        let mut data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.prepaid_amount, "5.00");
+        assert_eq!(data.amount_due(), "7.00");
+        assert!(data.has_prepaid());
+        assert!(data.has_separate_amount_due());
+
+        // Without a PayableAmount the due figure is derived, not the total.
+        data.payable_amount.clear();
+        assert_eq!(data.amount_due(), "7.00");
+    }
+
+    #[test]
+    fn parses_the_type_code_and_derives_the_title() {
+        let xml = SAMPLE_XML.replace(
+            "  <cbc:IssueDate>",
+            "  <cbc:InvoiceTypeCode>381</cbc:InvoiceTypeCode>\n  <cbc:IssueDate>",
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.type_code, "381");
+        assert_eq!(data.type_label(), Some("Credit note"));
+        assert_eq!(data.title(), "CREDIT NOTE");
+        assert!(!data.is_corrected());
+
+        let xml = xml.replace(">381<", ">384<");
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.type_label(), Some("Corrected invoice"));
+        assert_eq!(data.title(), "INVOICE");
+        assert!(data.is_corrected());
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.type_code.is_empty());
+        assert_eq!(data.type_label(), None);
+        assert_eq!(data.title(), "INVOICE");
+    }
+
+    #[test]
+    fn parses_tax_point_date_apart_from_issue_date() {
+        let xml = SAMPLE_XML.replace(
+            "  <cbc:IssueDate>2024-01-01</cbc:IssueDate>\n",
+            "  <cbc:IssueDate>2024-01-01</cbc:IssueDate>\n  <cbc:TaxPointDate>2023-12-31</cbc:TaxPointDate>\n",
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.issue_date, "2024-01-01");
+        assert_eq!(data.tax_point_date, "2023-12-31");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.tax_point_date.is_empty());
+    }
+
+    #[test]
+    fn parses_delivery_date_apart_from_issue_date() {
+        let xml = SAMPLE_XML.replace(
+            "  <cac:AccountingSupplierParty>",
+            r#"  <cac:Delivery>
+    <cbc:ActualDeliveryDate>2023-12-28</cbc:ActualDeliveryDate>
+  </cac:Delivery>
+  <cac:AccountingSupplierParty>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.issue_date, "2024-01-01");
+        assert_eq!(data.delivery_date, "2023-12-28");
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(data.delivery_date.is_empty());
+    }
+
+    #[test]
+    fn ignores_same_named_elements_from_other_namespaces() {
+        let xml = SAMPLE_XML
+            .replace(
+                "  <cbc:ID>INV-1</cbc:ID>",
+                r#"  <ext:UBLExtensions xmlns:ext="urn:oasis:names:specification:ubl:schema:xsd:CommonExtensionComponents-2">
+    <ext:UBLExtension>
+      <ext:ExtensionContent>
+        <acme:ID xmlns:acme="urn:example:acme">EXT-99</acme:ID>
+      </ext:ExtensionContent>
+    </ext:UBLExtension>
+  </ext:UBLExtensions>
+  <cbc:ID>INV-1</cbc:ID>"#,
+            )
+            .replace(
+                "      <cbc:Name>Supplier Inc</cbc:Name>",
+                r#"      <acme:Name xmlns:acme="urn:example:acme">Internal alias</acme:Name>
+      <acme:CompanyID xmlns:acme="urn:example:acme">ACME-7</acme:CompanyID>
+      <cbc:Name>Supplier Inc</cbc:Name>"#,
+            );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.invoice_number, "INV-1");
+        assert_eq!(data.supplier_name, "Supplier Inc");
+        assert_eq!(data.supplier_vat, "VAT123");
+    }
+
+    #[test]
+    fn captures_peppol_profile_identifiers() {
+        let xml = SAMPLE_XML.replace(
+            "  <cbc:ID>INV-1</cbc:ID>",
+            r#"  <cbc:CustomizationID>urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0</cbc:CustomizationID>
+  <cbc:ProfileID>urn:fdc:peppol.eu:2017:poacc:billing:01:1.0</cbc:ProfileID>
+  <cbc:ID>INV-1</cbc:ID>"#,
+        );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(
+            data.customization_id,
+            "urn:cen.eu:en16931:2017#compliant#urn:fdc:peppol.eu:2017:poacc:billing:3.0"
+        );
+        assert_eq!(data.profile_id, "urn:fdc:peppol.eu:2017:poacc:billing:01:1.0");
+        assert_eq!(data.invoice_number, "INV-1");
+    }
 }
@@ -1,65 +1,871 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use encoding_rs::{Encoding, UTF_8};
+use flate2::read::GzDecoder;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use notify::{EventKind, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use tracing::field::Empty;
+use tracing::{error, info, info_span, warn};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
 use walkdir::WalkDir;
 
-use ruble::{create_invoice_pdf, extract_embedded_pdf, parse_ubl_invoice, EmbeddedPdf};
+use ruble::{
+    EmbeddedPdf, InvoiceData, InvoiceStyle, PageSize, Theme, check_currencies, check_totals,
+    create_facturx_styled, create_invoice_pdf_bytes_styled, create_invoice_pdf_styled,
+    debug_field_map, extract_embedded_documents, parse_invoice, render_invoice_html, validate_font,
+    validate_invoice, validate_logo,
+};
+
+/// Output file name used when `--name-template` is not given.
+const DEFAULT_NAME_TEMPLATE: &str = "invoice_{id}_generated.pdf";
+
+/// Files handed to each worker per window while `--csv` or `--ndjson` is
+/// written, which caps how many finished invoices [`Export`] holds back.
+const EXPORT_WINDOW_PER_JOB: usize = 16;
 
 #[derive(Parser, Debug)]
-#[command(name = "ruble", version, about = "Convert UBL invoices to PDFs")]
+#[command(
+    name = "ruble",
+    version,
+    about = "Convert UBL invoices to PDFs",
+    args_conflicts_with_subcommands = true
+)]
 struct Cli {
-    /// Input directory to scan for UBL files
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Options of `convert`, which runs when no subcommand is given
+    #[command(flatten)]
+    convert: ConvertArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Convert invoices to PDF or HTML (the default)
+    Convert(Box<ConvertArgs>),
+    /// Only write the attachments embedded in invoices, without rendering them
+    Extract(ExtractArgs),
+    /// Parse invoices and print them as JSON, without writing any files
+    Inspect(InspectArgs),
+}
+
+/// Which files to read, shared by every subcommand.
+#[derive(Args, Debug)]
+struct SourceArgs {
+    /// Input directory or ZIP archive to scan for UBL files, or `-` to read one invoice from
+    /// stdin and write the result to stdout
     #[arg(default_value = ".")]
     input: PathBuf,
 
+    /// Comma-separated list of file extensions to treat as UBL
+    #[arg(long, default_value = "xml,ubl")]
+    extensions: String,
+
+    /// Skip files and folders whose path relative to the input matches this glob (repeatable)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Convert exactly the files listed in this file, one path per line (`-` reads stdin),
+    /// instead of scanning the input directory
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["exclude", "max_depth", "no_recursive"]
+    )]
+    files_from: Option<PathBuf>,
+
+    /// Descend at most this many directory levels; 1 only reads the input directory itself
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_depth: Option<u32>,
+
+    /// Descend into symlinked directories and convert symlinked files
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Only convert files directly in the input directory, same as `--max-depth 1`
+    #[arg(long, conflicts_with = "max_depth")]
+    no_recursive: bool,
+
+    /// Fail a gzip file or ZIP entry that decompresses to more than this many bytes
+    #[arg(long, value_name = "BYTES", default_value_t = 100_000_000)]
+    max_decompressed_bytes: u64,
+
+    /// Number of files to process in parallel (defaults to the number of CPUs)
+    #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+}
+
+#[derive(Args, Debug)]
+struct LogArgs {
+    /// Only print errors: no per-file log lines, progress bar or summary
+    #[arg(short, long, conflicts_with = "log_level")]
+    quiet: bool,
+
+    /// Most verbose log level to print (off, error, warn, info, debug, trace)
+    #[arg(long, default_value = "info")]
+    log_level: LevelFilter,
+
+    /// Format of the log lines written to stderr
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
     /// Output directory for generated PDFs (defaults to each file's directory)
     #[arg(short, long)]
     output: Option<PathBuf>,
 
-    /// Comma-separated list of file extensions to treat as UBL
-    #[arg(long, default_value = "xml,ubl")]
-    extensions: String,
+    /// Write every PDF directly into the output directory instead of mirroring subfolders
+    #[arg(long, requires = "output")]
+    flatten: bool,
+
+    /// File name for generated PDFs; placeholders: {id}, {invoice_number}, {issue_date},
+    /// {supplier_name}, {supplier_vat}, {currency}, {stem}
+    #[arg(long, default_value = DEFAULT_NAME_TEMPLATE)]
+    name_template: String,
 
-    /// Skip extracting embedded PDFs
+    /// Skip extracting embedded attachments
     #[arg(long)]
     no_embedded: bool,
+
+    /// Document format to generate for each invoice
+    #[arg(long, value_enum, default_value_t = OutputFormat::Pdf)]
+    format: OutputFormat,
+
+    /// Attach the source XML to the generated PDF, making a Factur-X file
+    #[arg(long)]
+    facturx: bool,
+
+    /// Keep running after the initial pass and convert invoices as they are added
+    #[arg(long, conflicts_with_all = ["csv", "ndjson", "report", "files_from"])]
+    watch: bool,
+
+    /// Print the parsed invoice data as JSON instead of generating PDFs
+    #[arg(long, conflicts_with = "quiet")]
+    json: bool,
+
+    /// Print every leaf element of each invoice, keyed by tag name, to debug empty fields
+    #[arg(long, conflicts_with_all = ["json", "csv", "ndjson"])]
+    debug_fields: bool,
+
+    /// Also write one CSV row per line item of every converted invoice to this file
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    csv: Option<PathBuf>,
+
+    /// Write a JSON manifest of the run (counts plus one record per file) to this path
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// Also write every converted invoice to this file as one JSON object per line
+    #[arg(long, value_name = "PATH", conflicts_with = "dry_run")]
+    ndjson: Option<PathBuf>,
+
+    /// Paper size of the generated PDFs
+    #[arg(long, value_enum, default_value_t = PaperSize::A4)]
+    page_size: PaperSize,
+
+    /// Colour theme of the generated PDFs
+    #[arg(long, value_enum, default_value_t = ThemeName::Classic)]
+    theme: ThemeName,
+
+    /// Add an EPC payment QR code when the invoice carries an IBAN
+    #[arg(long)]
+    qr: bool,
+
+    /// PNG or JPEG logo to draw in the top-right corner of each PDF
+    #[arg(long)]
+    logo: Option<PathBuf>,
+
+    /// TrueType/OpenType font to embed for text outside Western European scripts
+    #[arg(long)]
+    font: Option<PathBuf>,
+
+    /// Bold companion to --font for headings and totals
+    #[arg(long, requires = "font")]
+    font_bold: Option<PathBuf>,
+
+    /// Fail an invoice whose text has characters the font cannot draw instead of printing `?`
+    #[arg(long)]
+    strict_fonts: bool,
+
+    /// Shade every other row of the items table
+    #[arg(long)]
+    zebra: bool,
+
+    /// Right-align and reorder Arabic or Hebrew party names and addresses (use with --font)
+    #[arg(long)]
+    rtl: bool,
+
+    /// strftime-like pattern for the dates in the PDF header, e.g. `%d/%m/%Y` or `%-d %B %Y`
+    #[arg(long, value_name = "PATTERN")]
+    date_format: Option<String>,
+
+    /// Language of month names in --date-format and of currency symbol placement (en, fr, de)
+    #[arg(long, default_value = "en")]
+    date_locale: String,
+
+    /// Print amounts with the currency symbol (€, $, £) instead of the ISO code where known
+    #[arg(long)]
+    currency_symbol: bool,
+
+    /// What to do when a generated file or attachment already exists
+    #[arg(long, value_enum, default_value = "always")]
+    overwrite: Overwrite,
+
+    /// Shorthand for --overwrite if-newer
+    #[arg(long, conflicts_with = "overwrite")]
+    skip_existing: bool,
+
+    /// Parse every invoice and report failures without writing any files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Check EN 16931 mandatory fields and fail files that are missing any
+    #[arg(long, visible_alias = "strict")]
+    validate: bool,
+
+    #[command(flatten)]
+    log: LogArgs,
+
+    /// Skip rendering and only write embedded attachments (`extract`).
+    #[arg(skip)]
+    extract_only: bool,
+}
+
+#[derive(Args, Debug)]
+struct ExtractArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
+    /// Output directory for extracted attachments (defaults to each file's directory)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Write every attachment directly into the output directory instead of mirroring subfolders
+    #[arg(long, requires = "output")]
+    flatten: bool,
+
+    /// What to do when an attachment already exists
+    #[arg(long, value_enum, default_value = "always")]
+    overwrite: Overwrite,
+
+    /// Shorthand for --overwrite if-newer
+    #[arg(long, conflicts_with = "overwrite")]
+    skip_existing: bool,
+
+    /// Parse every invoice and report failures without writing any files
+    #[arg(long)]
+    dry_run: bool,
+
+    #[command(flatten)]
+    log: LogArgs,
+}
+
+#[derive(Args, Debug)]
+struct InspectArgs {
+    #[command(flatten)]
+    source: SourceArgs,
+
+    /// Check EN 16931 mandatory fields and fail files that are missing any
+    #[arg(long, visible_alias = "strict")]
+    validate: bool,
+
+    /// Print every leaf element of each invoice instead of the parsed data, keyed by tag name, to debug empty fields
+    #[arg(long)]
+    debug_fields: bool,
+
+    #[command(flatten)]
+    log: LogArgs,
+}
+
+impl ConvertArgs {
+    /// `convert` with every option at its default, for the other subcommands
+    /// to adjust.
+    fn defaults(source: SourceArgs, log: LogArgs) -> Self {
+        Self {
+            source,
+            output: None,
+            flatten: false,
+            name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+            no_embedded: false,
+            format: OutputFormat::Pdf,
+            facturx: false,
+            watch: false,
+            json: false,
+            debug_fields: false,
+            csv: None,
+            report: None,
+            ndjson: None,
+            page_size: PaperSize::A4,
+            theme: ThemeName::Classic,
+            qr: false,
+            logo: None,
+            font: None,
+            font_bold: None,
+            strict_fonts: false,
+            zebra: false,
+            rtl: false,
+            date_format: None,
+            date_locale: "en".to_string(),
+            currency_symbol: false,
+            overwrite: Overwrite::Always,
+            skip_existing: false,
+            dry_run: false,
+            validate: false,
+            log,
+            extract_only: false,
+        }
+    }
+}
+
+impl From<ExtractArgs> for ConvertArgs {
+    fn from(args: ExtractArgs) -> Self {
+        Self {
+            output: args.output,
+            flatten: args.flatten,
+            overwrite: args.overwrite,
+            skip_existing: args.skip_existing,
+            dry_run: args.dry_run,
+            extract_only: true,
+            ..Self::defaults(args.source, args.log)
+        }
+    }
+}
+
+impl From<InspectArgs> for ConvertArgs {
+    fn from(args: InspectArgs) -> Self {
+        Self {
+            json: !args.debug_fields,
+            debug_fields: args.debug_fields,
+            validate: args.validate,
+            ..Self::defaults(args.source, args.log)
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Pdf,
+    Html,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Pdf => "pdf",
+            Self::Html => "html",
+        }
+    }
+}
+
+/// `--overwrite`: whether an output file that already exists is rewritten.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum Overwrite {
+    /// Always rewrite existing outputs
+    Always,
+    /// Keep existing outputs and count the invoice as skipped
+    Never,
+    /// Rewrite outputs that are older than the source file
+    IfNewer,
+}
+
+impl Overwrite {
+    /// Whether `output` is left as it is rather than written again.
+    fn keeps(self, output: &Path, source_modified: Option<SystemTime>) -> bool {
+        match self {
+            Self::Always => false,
+            Self::Never => output.exists(),
+            Self::IfNewer => source_modified.is_some_and(|time| is_newer_than(output, time)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PaperSize {
+    A4,
+    Letter,
+}
+
+impl From<PaperSize> for PageSize {
+    fn from(size: PaperSize) -> Self {
+        match size {
+            PaperSize::A4 => Self::A4,
+            PaperSize::Letter => Self::Letter,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ThemeName {
+    Classic,
+    Minimal,
+    Dark,
+}
+
+impl From<ThemeName> for Theme {
+    fn from(theme: ThemeName) -> Self {
+        match theme {
+            ThemeName::Classic => Self::Classic,
+            ThemeName::Minimal => Self::Minimal,
+            ThemeName::Dark => Self::Dark,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
-    let extensions = parse_extensions(&cli.extensions);
-    let mut processed = 0usize;
-    let mut failures = 0usize;
+    let args = match cli.command {
+        None => cli.convert,
+        Some(Command::Convert(args)) => *args,
+        Some(Command::Extract(args)) => args.into(),
+        Some(Command::Inspect(args)) => args.into(),
+    };
+    run(args)
+}
 
-    for entry in WalkDir::new(&cli.input).into_iter().filter_map(Result::ok) {
-        if !entry.file_type().is_file() {
-            continue;
-        }
+fn run(cli: ConvertArgs) -> Result<()> {
+    // Only draw progress for interactive batch runs; JSON and piped output stay clean.
+    let progress = if !cli.json
+        && !cli.debug_fields
+        && !cli.log.quiet
+        && cli.source.input.as_os_str() != "-"
+        && io::stdout().is_terminal()
+    {
+        ProgressBar::with_draw_target(None, ProgressDrawTarget::stdout())
+    } else {
+        ProgressBar::hidden()
+    };
+    let log_level = if cli.log.quiet {
+        LevelFilter::ERROR
+    } else {
+        cli.log.log_level
+    };
+    init_logging(log_level, cli.log.log_format, &progress);
+    let extensions = parse_extensions(&cli.source.extensions);
+    let excludes = build_excludes(&cli.source.exclude)?;
+    let style = InvoiceStyle {
+        page_size: cli.page_size.into(),
+        payment_qr: cli.qr,
+        logo: cli.logo.as_deref().and_then(load_logo),
+        font: cli.font.as_deref().map(load_font).transpose()?,
+        font_bold: cli.font_bold.as_deref().map(load_font).transpose()?,
+        date_format: cli.date_format.clone(),
+        date_locale: Some(cli.date_locale.clone()),
+        strict_fonts: cli.strict_fonts,
+        zebra: cli.zebra,
+        rtl: cli.rtl,
+        use_currency_symbol: cli.currency_symbol,
+        ..InvoiceStyle::from_theme(cli.theme.into())
+    };
 
-        let path = entry.path();
-        if !matches_extension(path, &extensions) {
-            continue;
+    if cli.name_template.contains(['/', '\\']) {
+        anyhow::bail!("--name-template must be a file name, not a path");
+    }
+    fill_name_template(&cli.name_template, &[]).context("invalid --name-template")?;
+    if cli.facturx && cli.format != OutputFormat::Pdf {
+        anyhow::bail!("--facturx needs --format pdf");
+    }
+
+    if cli.source.input.as_os_str() == "-" {
+        if cli.extract_only {
+            anyhow::bail!("extract needs an input directory or ZIP archive, not stdin");
+        }
+        if cli.output.is_some()
+            || cli.csv.is_some()
+            || cli.ndjson.is_some()
+            || cli.report.is_some()
+            || cli.source.files_from.is_some()
+        {
+            anyhow::bail!(
+                "--output, --csv, --ndjson, --report and --files-from cannot be combined with \
+                 reading from stdin"
+            );
         }
+        return convert_stdin(
+            cli.json,
+            cli.debug_fields,
+            cli.validate,
+            cli.format,
+            cli.facturx,
+            &style,
+        );
+    }
+    // JSON, field dumps and dry runs never write to the output directory.
+    if let Some(root) = &cli.output
+        && !cli.json
+        && !cli.debug_fields
+        && !cli.dry_run
+    {
+        check_output_writable(root)?;
+    }
+    let is_zip = cli.source.input.is_file() && has_extension(&cli.source.input, "zip");
+    let max_depth = if cli.source.no_recursive {
+        Some(1)
+    } else {
+        cli.source.max_depth.map(|depth| depth as usize)
+    };
+    if cli.watch && !cli.source.input.is_dir() {
+        anyhow::bail!("--watch needs an input directory");
+    }
+    let (sources, input_root) = if let Some(list) = &cli.source.files_from {
+        (read_file_list(list)?, cli.source.input.as_path())
+    } else if is_zip {
+        let sources = read_zip_sources(
+            &cli.source.input,
+            &extensions,
+            &excludes,
+            cli.source.max_decompressed_bytes,
+        )?;
+        (sources, cli.source.input.parent().unwrap_or(Path::new(".")))
+    } else {
+        let sources = WalkDir::new(&cli.source.input)
+            .max_depth(max_depth.unwrap_or(usize::MAX))
+            .follow_links(cli.source.follow_symlinks)
+            .sort_by_file_name()
+            .into_iter()
+            // Pruning here keeps excluded folders from being walked at all.
+            .filter_entry(|entry| {
+                entry.depth() == 0
+                    || entry
+                        .path()
+                        .strip_prefix(&cli.source.input)
+                        .map_or(true, |relative| !excludes.is_match(relative))
+            })
+            .filter_map(|entry| match entry {
+                Ok(entry) => (entry.file_type().is_file()
+                    && matches_extension(entry.path(), &extensions))
+                .then(|| Source::File(entry.into_path())),
+                // Cycles only show up when following links; report them as
+                // failures rather than silently skipping part of the tree.
+                Err(err) if err.loop_ancestor().is_some() => Some(Source::Unwalkable {
+                    path: err.path().unwrap_or(&cli.source.input).to_path_buf(),
+                    error: err,
+                }),
+                Err(_) => None,
+            })
+            .collect();
+        (sources, cli.source.input.as_path())
+    };
+    let total = sources.len();
 
-        match process_file(path, cli.output.as_ref(), !cli.no_embedded) {
-            Ok(_) => processed += 1,
-            Err(err) => {
-                failures += 1;
-                eprintln!("ERROR {}: {err:#}", path.display());
-            }
+    progress.set_style(
+        ProgressStyle::with_template("{wide_bar} {pos}/{len} files, {msg} (ETA {eta})")
+            .context("progress bar template")?,
+    );
+    progress.set_message("0 failed");
+    progress.set_length(total as u64);
+    let failed = AtomicUsize::new(0);
+
+    let names = OutputNames::default();
+    // Zero lets rayon pick one thread per CPU.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(cli.source.jobs.map_or(0, usize::from))
+        .build()
+        .context("start worker threads")?;
+    let overwrite = if cli.skip_existing {
+        Overwrite::IfNewer
+    } else {
+        cli.overwrite
+    };
+    let input_root = absolute_path(input_root);
+    let options = ProcessOptions {
+        input_root: &input_root,
+        output_root: cli.output.as_deref(),
+        flatten: cli.flatten,
+        name_template: &cli.name_template,
+        format: cli.format,
+        facturx: cli.facturx,
+        render: !cli.extract_only,
+        max_decompressed_bytes: cli.source.max_decompressed_bytes,
+        names: &names,
+        extract_embedded: !cli.no_embedded,
+        dry_run: cli.dry_run,
+        validate: cli.validate,
+        json: cli.json,
+        debug_fields: cli.debug_fields,
+        overwrite,
+        style: &style,
+    };
+    let skipped = AtomicUsize::new(0);
+    let export = Mutex::new(Export::open(cli.csv.as_deref(), cli.ndjson.as_deref())?);
+    let records = cli.report.as_ref().map(|_| Mutex::new(Vec::new()));
+    // With an export, files go to the workers one window at a time, so at
+    // most a window of finished invoices waits on a slow earlier one.
+    // Bridging hands each window out in input order.
+    let window = if cli.csv.is_some() || cli.ndjson.is_some() {
+        pool.current_num_threads() * EXPORT_WINDOW_PER_JOB
+    } else {
+        total.max(1)
+    };
+    let mut sources = sources.into_iter().enumerate();
+    loop {
+        let batch: Vec<_> = sources.by_ref().take(window).collect();
+        if batch.is_empty() {
+            break;
         }
+        pool.install(|| {
+            batch.into_iter().par_bridge().for_each(|(index, source)| {
+                let label = records.as_ref().map(|_| source.label());
+                let result = convert_source(source, &options);
+                if let (Some(records), Some(label)) = (&records, label) {
+                    let record = ReportRecord::new(label, &result);
+                    records
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .push((index, record));
+                }
+                let data = match result {
+                    Ok(converted) => {
+                        if converted.skipped {
+                            skipped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Some(converted.data)
+                    }
+                    Err(_) => {
+                        let failed = failed.fetch_add(1, Ordering::Relaxed) + 1;
+                        progress.set_message(format!("{failed} failed"));
+                        None
+                    }
+                };
+                export
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .submit(index, data);
+                progress.inc(1);
+            });
+        });
+    }
+    progress.finish_and_clear();
+    export
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .finish()?;
+    let failures = failed.into_inner();
+    let processed = total - failures;
+    let skipped = skipped.into_inner();
+    if let (Some(report_path), Some(records)) = (&cli.report, records) {
+        let mut records = records.into_inner().unwrap_or_else(PoisonError::into_inner);
+        records.sort_by_key(|(index, _)| *index);
+        let report = Report {
+            processed,
+            failures,
+            skipped,
+            files: records.into_iter().map(|(_, record)| record).collect(),
+        };
+        write_report(report_path, &report)
+            .with_context(|| format!("write {}", report_path.display()))?;
+        info!(output = %report_path.display(), "wrote report");
     }
 
-    println!("Processed {processed} file(s) with {failures} failure(s).");
+    let mut summary = format!("Processed {processed} file(s) with {failures} failure(s).");
+    match overwrite {
+        Overwrite::Always => {}
+        Overwrite::Never => {
+            summary.push_str(&format!(
+                " Skipped {skipped} file(s) with existing outputs."
+            ));
+        }
+        Overwrite::IfNewer => {
+            summary.push_str(&format!(" Skipped {skipped} up-to-date file(s)."));
+        }
+    }
+    // Keep stdout pure JSON in --json mode. In quiet mode failures were
+    // already logged as errors and the exit code reports them, so the
+    // summary is left out.
+    if cli.json || cli.debug_fields {
+        eprintln!("{summary}");
+    } else if !cli.log.quiet {
+        println!("{summary}");
+    }
+    if cli.watch {
+        return watch(
+            &cli.source.input,
+            max_depth,
+            &extensions,
+            &excludes,
+            &options,
+        );
+    }
     if failures > 0 {
         anyhow::bail!("One or more files failed to process");
     }
     Ok(())
 }
 
+/// A successfully parsed invoice and whether its outputs were written.
+struct Converted {
+    data: InvoiceData,
+    /// The generated PDF or HTML file, if the run writes one.
+    output: Option<PathBuf>,
+    /// The outputs were already newer than the source (`--skip-existing`).
+    skipped: bool,
+}
+
+impl Converted {
+    /// An invoice that was only parsed, e.g. for `--json` or `--dry-run`.
+    fn parsed(data: InvoiceData) -> Self {
+        Self {
+            data,
+            output: None,
+            skipped: false,
+        }
+    }
+}
+
+/// Converts one source inside its own `file` span, logging a failure there.
+fn convert_source(source: Source, options: &ProcessOptions) -> Result<Converted> {
+    let label = source.label();
+    let span = info_span!(
+        "file",
+        path = %label,
+        invoice_number = Empty,
+        outcome = Empty
+    );
+    let _entered = span.enter();
+    let path = source.path();
+    let modified = source.modified();
+    let result = source.read(options.max_decompressed_bytes).and_then(|xml| {
+        if options.debug_fields {
+            print_field_map(&label, &xml)?;
+            let data = parse_checked(&xml, options.validate)?;
+            return Ok(Converted::parsed(data));
+        }
+        if options.json {
+            let data = print_json(&xml, options.validate)?;
+            return Ok(Converted::parsed(data));
+        }
+        process_invoice(&path, &xml, modified, options)
+    });
+    match &result {
+        Ok(converted) => {
+            span.record("outcome", if converted.skipped { "skipped" } else { "ok" });
+        }
+        Err(err) => {
+            span.record("outcome", "failed");
+            // Below info level (`--quiet`, `--log-level error`) the span is
+            // filtered out, so the error carries the path itself.
+            if span.is_disabled() {
+                error!(path = %label, "{err:#}");
+            } else {
+                error!("{err:#}");
+            }
+        }
+    }
+    result
+}
+
+/// How long a path must stay quiet before it is converted, so files that
+/// are still being written are not parsed half-way.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Converts matching files under `root`, at most `max_depth` levels down,
+/// whenever they are created or modified. Only returns if the watcher itself
+/// fails.
+fn watch(
+    root: &Path,
+    max_depth: Option<usize>,
+    extensions: &HashSet<String>,
+    excludes: &GlobSet,
+    options: &ProcessOptions,
+) -> Result<()> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("start file watcher")?;
+    let mode = if max_depth == Some(1) {
+        RecursiveMode::NonRecursive
+    } else {
+        RecursiveMode::Recursive
+    };
+    let in_scope = |path: &Path| {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return true;
+        };
+        max_depth.is_none_or(|max_depth| relative.components().count() <= max_depth)
+            && !is_excluded(excludes, relative)
+    };
+    watcher
+        .watch(root, mode)
+        .with_context(|| format!("watch {}", root.display()))?;
+    info!(path = %root.display(), "watching for new invoices");
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        match events.recv_timeout(WATCH_DEBOUNCE) {
+            Ok(Ok(event)) => {
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    for path in event.paths {
+                        if matches_extension(&path, extensions) && in_scope(&path) {
+                            pending.insert(path, Instant::now());
+                        }
+                    }
+                }
+            }
+            Ok(Err(err)) => warn!("file watcher: {err}"),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => anyhow::bail!("file watcher stopped"),
+        }
+
+        let ready: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, seen)| seen.elapsed() >= WATCH_DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in ready {
+            pending.remove(&path);
+            if !path.is_file() {
+                continue;
+            }
+            // A file converted again should overwrite its earlier output
+            // rather than get a collision suffix, so names are per event.
+            let names = OutputNames::default();
+            let options = ProcessOptions {
+                names: &names,
+                ..*options
+            };
+            let _ = convert_source(Source::File(path), &options);
+        }
+    }
+}
+
+fn build_excludes(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob =
+            Glob::new(pattern).with_context(|| format!("invalid --exclude pattern {pattern:?}"))?;
+        builder.add(glob);
+    }
+    builder.build().context("build --exclude patterns")
+}
+
+/// Whether `relative` or any folder above it matches an `--exclude` glob.
+fn is_excluded(excludes: &GlobSet, relative: &Path) -> bool {
+    relative
+        .ancestors()
+        .filter(|path| !path.as_os_str().is_empty())
+        .any(|path| excludes.is_match(path))
+}
+
 fn parse_extensions(input: &str) -> HashSet<String> {
     input
         .split(',')
@@ -68,48 +874,939 @@ fn parse_extensions(input: &str) -> HashSet<String> {
         .collect()
 }
 
+/// Matches `invoice.xml` as well as gzip-compressed `invoice.xml.gz`.
 fn matches_extension(path: &Path, extensions: &HashSet<String>) -> bool {
+    let path = if is_gzip(path) {
+        Path::new(path.file_stem().unwrap_or_default())
+    } else {
+        path
+    };
     match path.extension().and_then(|ext| ext.to_str()) {
         Some(ext) => extensions.contains(&ext.to_ascii_lowercase()),
         None => false,
     }
 }
 
-fn process_file(path: &Path, output_root: Option<&PathBuf>, extract_embedded: bool) -> Result<()> {
-    let xml = fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
-    let data = parse_ubl_invoice(&xml).with_context(|| "parse UBL invoice")?;
+fn is_gzip(path: &Path) -> bool {
+    has_extension(path, "gz")
+}
+
+/// `path` resolved against the current directory without touching the
+/// filesystem; an empty path is the current directory.
+fn absolute_path(path: &Path) -> PathBuf {
+    let path = if path.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        path
+    };
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+}
+
+/// The source file name without its extension (and without `.gz`).
+fn source_stem(path: &Path) -> String {
+    let name = if is_gzip(path) {
+        path.file_stem().map(Path::new)
+    } else {
+        Some(path)
+    };
+    name.and_then(Path::file_stem)
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Reads an invoice file, transparently decompressing `.gz` files.
+fn read_xml(path: &Path, max_decompressed_bytes: u64) -> Result<String> {
+    let context = || format!("read {}", path.display());
+    let bytes = if is_gzip(path) {
+        let file = fs::File::open(path).with_context(context)?;
+        read_limited(GzDecoder::new(file), max_decompressed_bytes).with_context(context)?
+    } else {
+        fs::read(path).with_context(context)?
+    };
+    decode_xml(&bytes).with_context(context)
+}
+
+/// Reads a decompressing reader to the end, failing as soon as it yields
+/// more than `limit` bytes so a small archive cannot expand without bound.
+fn read_limited(reader: impl Read, limit: u64) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > limit {
+        anyhow::bail!("decompresses to more than {limit} bytes (see --max-decompressed-bytes)");
+    }
+    Ok(bytes)
+}
+
+/// Decodes raw XML to UTF-8. A byte order mark wins; otherwise the encoding
+/// named in the XML declaration is used, defaulting to UTF-8.
+fn decode_xml(bytes: &[u8]) -> Result<String> {
+    // A byte order mark overrides the declaration, which cannot be read as
+    // ASCII anyway in the UTF-16 files some Windows ERPs export.
+    let encoding = match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => encoding,
+        None => declared_encoding(bytes)
+            .map(|label| {
+                Encoding::for_label(label.as_bytes())
+                    .with_context(|| format!("unsupported XML encoding {label:?}"))
+            })
+            .transpose()?
+            .unwrap_or(UTF_8),
+    };
+    // decode() strips the BOM.
+    let (xml, encoding, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        anyhow::bail!("invalid {} in XML", encoding.name());
+    }
+    Ok(xml.into_owned())
+}
+
+/// The `encoding` pseudo-attribute of an ASCII-compatible XML declaration.
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    let declaration = bytes.strip_prefix(b"<?xml")?;
+    let end = declaration.windows(2).position(|pair| pair == b"?>")?;
+    let declaration = std::str::from_utf8(&declaration[..end]).ok()?;
+    let value = declaration.split_once("encoding")?.1.trim_start();
+    let value = value.strip_prefix('=')?.trim_start();
+    let quote = value
+        .chars()
+        .next()
+        .filter(|ch| *ch == '"' || *ch == '\'')?;
+    let value = &value[1..];
+    Some(value[..value.find(quote)?].to_string())
+}
+
+struct ProcessOptions<'a> {
+    input_root: &'a Path,
+    output_root: Option<&'a Path>,
+    flatten: bool,
+    name_template: &'a str,
+    format: OutputFormat,
+    facturx: bool,
+    /// Write the PDF or HTML document; `extract` only writes attachments.
+    render: bool,
+    max_decompressed_bytes: u64,
+    names: &'a OutputNames,
+    extract_embedded: bool,
+    dry_run: bool,
+    validate: bool,
+    json: bool,
+    debug_fields: bool,
+    overwrite: Overwrite,
+    style: &'a InvoiceStyle,
+}
+
+/// One invoice to convert: a file on disk or an entry of a ZIP archive.
+enum Source {
+    File(PathBuf),
+    /// Entries are read up front because a `ZipArchive` cannot be shared
+    /// between worker threads.
+    ZipEntry {
+        archive: PathBuf,
+        name: PathBuf,
+        xml: Result<String>,
+    },
+    /// A directory entry the walk could not follow, such as a symlink cycle;
+    /// converting it reports the error.
+    Unwalkable {
+        path: PathBuf,
+        error: walkdir::Error,
+    },
+}
+
+impl Source {
+    fn label(&self) -> String {
+        match self {
+            Self::File(path) | Self::Unwalkable { path, .. } => path.display().to_string(),
+            Self::ZipEntry { archive, name, .. } => {
+                format!("{}!/{}", archive.display(), name.display())
+            }
+        }
+    }
+
+    /// Where the invoice logically lives; ZIP entries sit in a folder next to
+    /// the archive, so output placement and naming treat them like files.
+    fn path(&self) -> PathBuf {
+        match self {
+            Self::File(path) | Self::Unwalkable { path, .. } => path.clone(),
+            Self::ZipEntry { archive, name, .. } => {
+                archive.parent().unwrap_or(Path::new(".")).join(name)
+            }
+        }
+    }
+
+    /// When the source last changed; a ZIP entry counts as changed with its archive.
+    fn modified(&self) -> Option<SystemTime> {
+        let file = match self {
+            Self::File(path) => path,
+            Self::ZipEntry { archive, .. } => archive,
+            Self::Unwalkable { .. } => return None,
+        };
+        fs::metadata(file).and_then(|meta| meta.modified()).ok()
+    }
+
+    fn read(self, max_decompressed_bytes: u64) -> Result<String> {
+        match self {
+            Self::File(path) => read_xml(&path, max_decompressed_bytes),
+            Self::ZipEntry { xml, .. } => xml,
+            Self::Unwalkable { error, .. } => Err(error.into()),
+        }
+    }
+}
+
+/// Reads the `--files-from` list: one path per line, relative paths taken
+/// from the current directory. Lines are trimmed, so CRLF lists and trailing
+/// spaces work, and blank lines are ignored; listed files are not checked
+/// against `--extensions`.
+fn read_file_list(path: &Path) -> Result<Vec<Source>> {
+    let list = if path.as_os_str() == "-" {
+        let mut list = String::new();
+        io::stdin()
+            .read_to_string(&mut list)
+            .context("read file list from stdin")?;
+        list
+    } else {
+        fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?
+    };
+    Ok(list
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| Source::File(PathBuf::from(line)))
+        .collect())
+}
+
+/// Reads every entry of a ZIP archive that matches the invoice extensions.
+/// A broken entry is kept as a failed source rather than aborting the archive.
+fn read_zip_sources(
+    archive_path: &Path,
+    extensions: &HashSet<String>,
+    excludes: &GlobSet,
+    max_decompressed_bytes: u64,
+) -> Result<Vec<Source>> {
+    let file =
+        fs::File::open(archive_path).with_context(|| format!("read {}", archive_path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("open ZIP archive {}", archive_path.display()))?;
+
+    let mut sources = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = match archive.by_index(index) {
+            Ok(entry) => entry,
+            Err(err) => {
+                sources.push(Source::ZipEntry {
+                    archive: archive_path.to_path_buf(),
+                    name: PathBuf::from(format!("#{index}")),
+                    xml: Err(err).context("read ZIP entry"),
+                });
+                continue;
+            }
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        // enclosed_name() rejects absolute paths and `..` so entries cannot
+        // place output outside the archive's folder.
+        let Some(name) = entry.enclosed_name() else {
+            warn!("skipping ZIP entry with unsafe path {:?}", entry.name());
+            continue;
+        };
+        if !matches_extension(&name, extensions) || is_excluded(excludes, &name) {
+            continue;
+        }
+        let xml = read_limited(&mut entry, max_decompressed_bytes)
+            .context("read ZIP entry")
+            .and_then(|bytes| decode_xml(&bytes));
+        sources.push(Source::ZipEntry {
+            archive: archive_path.to_path_buf(),
+            name,
+            xml,
+        });
+    }
+    Ok(sources)
+}
+
+fn process_invoice(
+    path: &Path,
+    xml: &str,
+    source_modified: Option<SystemTime>,
+    options: &ProcessOptions,
+) -> Result<Converted> {
+    let data = parse_checked(xml, options.validate)?;
+    if options.dry_run {
+        info!("would process");
+        return Ok(Converted::parsed(data));
+    }
 
-    let out_dir = output_root
-        .map(PathBuf::from)
-        .or_else(|| path.parent().map(PathBuf::from))
-        .unwrap_or_else(|| PathBuf::from("."));
+    let source_dir = path.parent().unwrap_or(Path::new("."));
+    let out_dir = match options.output_root {
+        Some(root) if options.flatten => root.to_path_buf(),
+        // Recreate the input subfolders; anything not under the input root stays
+        // flat. Both sides are absolute so `--files-from` entries mirror however
+        // they are spelled.
+        Some(root) => match absolute_path(source_dir).strip_prefix(options.input_root) {
+            Ok(relative) => root.join(relative),
+            Err(_) => root.to_path_buf(),
+        },
+        None => source_dir.to_path_buf(),
+    };
     fs::create_dir_all(&out_dir).with_context(|| format!("create {}", out_dir.display()))?;
 
+    let stem = source_stem(path);
     let invoice_id = if data.invoice_number.is_empty() {
-        path.file_stem()
-            .and_then(|stem| stem.to_str())
-            .unwrap_or("invoice")
-            .to_string()
+        if stem.is_empty() { "invoice" } else { &stem }
+    } else {
+        &data.invoice_number
+    };
+    let generated = if options.render {
+        let values = [
+            ("id", invoice_id),
+            ("invoice_number", &data.invoice_number),
+            ("issue_date", &data.issue_date),
+            ("supplier_name", &data.supplier_name),
+            ("supplier_vat", &data.supplier_vat),
+            ("currency", &data.currency),
+            ("stem", &stem),
+        ];
+        let mut file_name = fill_name_template(options.name_template, &values)?;
+        // The default template ends in .pdf; other formats swap the extension.
+        if options.format != OutputFormat::Pdf && file_name.to_ascii_lowercase().ends_with(".pdf") {
+            file_name.truncate(file_name.len() - ".pdf".len());
+        }
+        let extension = options.format.extension();
+        if !file_name
+            .to_ascii_lowercase()
+            .ends_with(&format!(".{extension}"))
+        {
+            file_name.push('.');
+            file_name.push_str(extension);
+        }
+        Some(options.names.claim(out_dir.join(file_name)))
+    } else {
+        None
+    };
+
+    let documents = if options.extract_embedded {
+        extract_embedded_documents(xml)?
     } else {
-        data.invoice_number.clone()
+        Vec::new()
     };
+    if generated.is_none() && documents.is_empty() {
+        info!("no embedded attachments");
+        return Ok(Converted::parsed(data));
+    }
+    let embedded_paths: Vec<PathBuf> = documents
+        .iter()
+        .enumerate()
+        .map(|(index, embedded)| {
+            // Index suffixes keep several fallback-named attachments apart.
+            let suffix = if documents.len() > 1 {
+                format!("_{}", index + 1)
+            } else {
+                String::new()
+            };
+            let extension = extension_for_mime(&embedded.mime_code);
+            let name = match embedded.filename.as_deref().and_then(sanitize_filename) {
+                // Take the extension from the MIME type when the name lacks one.
+                Some(name) if Path::new(&name).extension().is_none() => {
+                    format!("{name}.{extension}")
+                }
+                Some(name) => name,
+                None => format!(
+                    "invoice_{}_embedded{suffix}.{extension}",
+                    sanitize_name_part(invoice_id)
+                ),
+            };
+            options.names.claim(out_dir.join(name))
+        })
+        .collect();
 
-    let generated_pdf = out_dir.join(format!("invoice_{invoice_id}_generated.pdf"));
-    create_invoice_pdf(&data, &generated_pdf)?;
-    println!("OK Generated PDF: {}", generated_pdf.display());
+    // The policy applies to each output, so a missing attachment is still
+    // written next to a kept PDF; only an invoice with every output kept
+    // counts as skipped.
+    let keeps = |output: &PathBuf| options.overwrite.keeps(output, source_modified);
+    let render = generated.as_ref().is_some_and(|output| !keeps(output));
+    if !render && embedded_paths.iter().all(keeps) {
+        info!(overwrite = ?options.overwrite, "outputs kept, skipped");
+        return Ok(Converted {
+            data,
+            output: generated,
+            skipped: true,
+        });
+    }
 
-    if extract_embedded {
-        if let Some(embedded) = extract_embedded_pdf(&xml)? {
-            let embedded_path = out_dir.join(format!("invoice_{invoice_id}_embedded.pdf"));
-            write_embedded_pdf(&embedded, &embedded_path)?;
-            println!("OK Embedded PDF: {}", embedded_path.display());
+    if let Some(generated) = generated.as_ref().filter(|_| render) {
+        render_document(&data, xml, generated, options)?;
+    }
+
+    for (embedded, embedded_path) in documents.iter().zip(&embedded_paths) {
+        if keeps(embedded_path) {
+            continue;
         }
+        write_embedded_document(embedded, embedded_path)?;
+        info!(
+            output = %embedded_path.display(),
+            mime_code = %embedded.mime_code,
+            "extracted attachment"
+        );
     }
 
+    Ok(Converted {
+        data,
+        output: generated,
+        skipped: false,
+    })
+}
+
+/// Writes the PDF (or Factur-X PDF) or HTML rendering of one invoice.
+fn render_document(
+    data: &InvoiceData,
+    xml: &str,
+    generated: &Path,
+    options: &ProcessOptions,
+) -> Result<()> {
+    let written = match options.format {
+        OutputFormat::Pdf if options.facturx => create_facturx_styled(data, options.style, xml)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| fs::write(generated, bytes).map_err(anyhow::Error::from)),
+        OutputFormat::Pdf => {
+            create_invoice_pdf_styled(data, options.style, generated).map_err(anyhow::Error::from)
+        }
+        OutputFormat::Html => {
+            fs::write(generated, render_invoice_html(data)).map_err(anyhow::Error::from)
+        }
+    };
+    written.with_context(|| format!("write {}", generated.display()))?;
+    let extension = options.format.extension().to_ascii_uppercase();
+    info!(output = %generated.display(), "generated {extension}");
     Ok(())
 }
 
-fn write_embedded_pdf(embedded: &EmbeddedPdf, output_path: &Path) -> Result<()> {
+fn is_newer_than(path: &Path, time: SystemTime) -> bool {
+    fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .is_ok_and(|modified| modified > time)
+}
+
+/// Output paths handed out during this run, so two source files (or two
+/// attachments) that map to the same name never overwrite each other.
+#[derive(Default)]
+struct OutputNames(Mutex<HashSet<PathBuf>>);
+
+impl OutputNames {
+    /// Reserves `path`, appending `_2`, `_3`, ... to the file stem while it is taken.
+    fn claim(&self, path: PathBuf) -> PathBuf {
+        let mut claimed = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut candidate = path.clone();
+        let mut counter = 2;
+        while claimed.contains(&candidate) {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let name = match path.extension() {
+                Some(ext) => format!("{stem}_{counter}.{}", ext.to_string_lossy()),
+                None => format!("{stem}_{counter}"),
+            };
+            candidate = path.with_file_name(name);
+            counter += 1;
+        }
+        claimed.insert(candidate.clone());
+        candidate
+    }
+}
+
+/// Sends log lines to stderr so stdout only carries PDFs, JSON, and the summary.
+/// Each file's span is logged when it closes, carrying its final `outcome`.
+fn init_logging(level: LevelFilter, format: LogFormat, progress: &ProgressBar) {
+    let progress = progress.clone();
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(move || ProgressLogWriter(progress.clone()))
+        .with_ansi(io::stderr().is_terminal())
+        .with_target(false)
+        .with_span_events(FmtSpan::CLOSE);
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+/// Writes log lines to stderr with the progress bar lifted out of the way, so
+/// the two never end up on the same terminal line.
+struct ProgressLogWriter(ProgressBar);
+
+impl Write for ProgressLogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.suspend(|| io::stderr().write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Parses an invoice and, when asked, rejects it if mandatory fields are missing.
+fn parse_checked(xml: &str, validate: bool) -> Result<InvoiceData> {
+    let data = parse_invoice(xml).with_context(|| "parse invoice")?;
+    tracing::Span::current().record("invoice_number", data.invoice_number.as_str());
+    if validate {
+        // Arithmetic mismatches point at a sloppy source document but do not
+        // stop conversion.
+        for problem in check_totals(&data) {
+            warn!("totals mismatch: {problem}");
+        }
+        for problem in check_currencies(&data) {
+            warn!("currency mismatch: {problem}");
+        }
+    }
+    if validate && let Err(problems) = validate_invoice(&data) {
+        anyhow::bail!("invalid invoice: {}", problems.join("; "));
+    }
+    Ok(data)
+}
+
+fn print_json(xml: &str, validate: bool) -> Result<InvoiceData> {
+    let data = parse_checked(xml, validate)?;
+    let json = serde_json::to_string_pretty(&data).context("serialize invoice")?;
+    println!("{json}");
+    Ok(data)
+}
+
+/// The `--report` manifest, written once the batch is done.
+#[derive(serde::Serialize)]
+struct Report {
+    processed: usize,
+    failures: usize,
+    skipped: usize,
+    /// One record per input file, in input order.
+    files: Vec<ReportRecord>,
+}
+
+#[derive(serde::Serialize)]
+struct ReportRecord {
+    source: String,
+    output: Option<PathBuf>,
+    invoice_number: Option<String>,
+    /// `ok`, `skipped` or `failed`, like the `outcome` log field.
+    status: &'static str,
+    error: Option<String>,
+}
+
+impl ReportRecord {
+    fn new(source: String, result: &Result<Converted>) -> Self {
+        match result {
+            Ok(converted) => Self {
+                source,
+                output: converted.output.clone(),
+                invoice_number: Some(converted.data.invoice_number.clone()),
+                status: if converted.skipped { "skipped" } else { "ok" },
+                error: None,
+            },
+            Err(err) => Self {
+                source,
+                output: None,
+                invoice_number: None,
+                status: "failed",
+                error: Some(format!("{err:#}")),
+            },
+        }
+    }
+}
+
+fn write_report(path: &Path, report: &Report) -> Result<()> {
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    serde_json::to_writer_pretty(&mut file, report)?;
+    file.write_all(b"\n")?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Prints the `--debug-fields` dump of one document as a JSON object.
+fn print_field_map(source: &str, xml: &str) -> Result<()> {
+    let fields = debug_field_map(xml)?;
+    let dump = serde_json::json!({ "source": source, "fields": fields });
+    let json = serde_json::to_string_pretty(&dump).context("serialize fields")?;
+    println!("{json}");
+    Ok(())
+}
+
+/// The `--csv` and `--ndjson` files, written while the batch runs.
+///
+/// Invoices are released in input order: one that finishes early waits in
+/// `pending` until those before it are done. The batch hands out files in
+/// windows of [`EXPORT_WINDOW_PER_JOB`] per worker, so `pending` never holds
+/// more than one window however large the tree is.
+struct Export {
+    csv: Option<(PathBuf, csv::Writer<fs::File>)>,
+    ndjson: Option<(PathBuf, BufWriter<fs::File>)>,
+    next: usize,
+    /// Finished invoices waiting for an earlier one; `None` marks a failure.
+    pending: BTreeMap<usize, Option<InvoiceData>>,
+    /// The first write error; later invoices are dropped once it is set.
+    error: Option<anyhow::Error>,
+}
+
+impl Export {
+    fn open(csv_path: Option<&Path>, ndjson_path: Option<&Path>) -> Result<Self> {
+        let csv = csv_path
+            .map(|path| -> Result<_> {
+                let mut writer = csv::Writer::from_path(path)
+                    .with_context(|| format!("create {}", path.display()))?;
+                writer.write_record([
+                    "invoice_number",
+                    "issue_date",
+                    "supplier",
+                    "description",
+                    "quantity",
+                    "unit_price",
+                    "total",
+                    "currency",
+                    "order_line_id",
+                    "seller_item_id",
+                    "buyer_item_id",
+                    "preceding_invoice",
+                ])?;
+                Ok((path.to_path_buf(), writer))
+            })
+            .transpose()?;
+        let ndjson = ndjson_path
+            .map(|path| -> Result<_> {
+                let file =
+                    fs::File::create(path).with_context(|| format!("create {}", path.display()))?;
+                Ok((path.to_path_buf(), BufWriter::new(file)))
+            })
+            .transpose()?;
+        Ok(Self {
+            csv,
+            ndjson,
+            next: 0,
+            pending: BTreeMap::new(),
+            error: None,
+        })
+    }
+
+    /// Hands over the outcome of the `index`-th source, writing it and any
+    /// invoices that were waiting on it.
+    fn submit(&mut self, index: usize, data: Option<InvoiceData>) {
+        if self.csv.is_none() && self.ndjson.is_none() {
+            return;
+        }
+        self.pending.insert(index, data);
+        while let Some(data) = self.pending.remove(&self.next) {
+            self.next += 1;
+            if self.error.is_none()
+                && let Some(data) = data
+                && let Err(err) = self.write(&data)
+            {
+                self.error = Some(err);
+            }
+        }
+    }
+
+    fn write(&mut self, data: &InvoiceData) -> Result<()> {
+        if let Some((path, writer)) = &mut self.csv {
+            write_csv_rows(writer, data).with_context(|| format!("write {}", path.display()))?;
+        }
+        if let Some((path, writer)) = &mut self.ndjson {
+            serde_json::to_writer(&mut *writer, data)
+                .map_err(io::Error::from)
+                .and_then(|()| writer.write_all(b"\n"))
+                .with_context(|| format!("write {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        if let Some((path, mut writer)) = self.csv {
+            writer
+                .flush()
+                .with_context(|| format!("write {}", path.display()))?;
+            info!(output = %path.display(), "wrote CSV");
+        }
+        if let Some((path, mut writer)) = self.ndjson {
+            writer
+                .flush()
+                .with_context(|| format!("write {}", path.display()))?;
+            info!(output = %path.display(), "wrote NDJSON");
+        }
+        Ok(())
+    }
+}
+
+/// Writes one row per line item. Amounts stay as they appear in the XML so
+/// the file can be fed straight into accounting tools.
+fn write_csv_rows(writer: &mut csv::Writer<fs::File>, data: &InvoiceData) -> csv::Result<()> {
+    for line in &data.lines {
+        let description = if line.description.is_empty() {
+            &line.name
+        } else {
+            &line.description
+        };
+        writer.write_record([
+            &data.invoice_number,
+            &data.issue_date,
+            &data.supplier_name,
+            description,
+            &line.quantity,
+            &line.unit_price,
+            &line.total,
+            &data.currency,
+            &line.order_line_id,
+            &line.seller_item_id,
+            &line.buyer_item_id,
+            &data.preceding_invoice_reference,
+        ])?;
+    }
+    Ok(())
+}
+
+/// Converts a single invoice read from stdin, writing only the PDF (or JSON)
+/// to stdout so the output can be piped.
+fn convert_stdin(
+    json: bool,
+    debug_fields: bool,
+    validate: bool,
+    format: OutputFormat,
+    facturx: bool,
+    style: &InvoiceStyle,
+) -> Result<()> {
+    let mut bytes = Vec::new();
+    io::stdin().read_to_end(&mut bytes).context("read stdin")?;
+    let xml = decode_xml(&bytes).context("read stdin")?;
+    if json {
+        return print_json(&xml, validate).map(drop);
+    }
+    if debug_fields {
+        return print_field_map("-", &xml);
+    }
+
+    let data = parse_checked(&xml, validate)?;
+    let document = match format {
+        OutputFormat::Pdf if facturx => {
+            create_facturx_styled(&data, style, &xml).context("generate Factur-X PDF")?
+        }
+        OutputFormat::Pdf => {
+            create_invoice_pdf_bytes_styled(&data, style).context("generate PDF")?
+        }
+        OutputFormat::Html => render_invoice_html(&data).into_bytes(),
+    };
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(&document).context("write stdout")?;
+    stdout.flush().context("write stdout")
+}
+
+/// Reads and checks a font once. Unlike the logo, a bad font is fatal: falling
+/// back to Helvetica would silently garble the text it was chosen for.
+fn load_font(path: &Path) -> Result<Vec<u8>> {
+    let bytes = fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    validate_font(&bytes).with_context(|| format!("load font {}", path.display()))?;
+    Ok(bytes)
+}
+
+/// Creates the `--output` directory and writes and removes a probe file in
+/// it, so a read-only or uncreatable location fails before any invoice is
+/// parsed instead of once per file.
+fn check_output_writable(root: &Path) -> Result<()> {
+    let probe = root.join(format!(".ruble-write-check-{}", std::process::id()));
+    fs::create_dir_all(root)
+        .and_then(|()| fs::File::create(&probe))
+        .and_then(|_| fs::remove_file(&probe))
+        .with_context(|| format!("output directory {} is not writable", root.display()))
+}
+
+/// Reads and checks the logo once, so a bad image only costs a warning
+/// instead of failing every file.
+fn load_logo(path: &Path) -> Option<Vec<u8>> {
+    let checked = fs::read(path)
+        .with_context(|| format!("read {}", path.display()))
+        .and_then(|bytes| {
+            validate_logo(&bytes).with_context(|| format!("decode {}", path.display()))?;
+            Ok(bytes)
+        });
+    match checked {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            warn!("skipping logo: {err:#}");
+            None
+        }
+    }
+}
+
+/// Substitutes `{placeholder}`s in an output file name template with the
+/// matching sanitized value. Known placeholders missing from `values` render
+/// blank; unknown ones are an error.
+fn fill_name_template(template: &str, values: &[(&str, &str)]) -> Result<String> {
+    const PLACEHOLDERS: [&str; 7] = [
+        "id",
+        "invoice_number",
+        "issue_date",
+        "supplier_name",
+        "supplier_vat",
+        "currency",
+        "stem",
+    ];
+
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .with_context(|| format!("unclosed placeholder in {template:?}"))?;
+        let key = &after[..end];
+        if !PLACEHOLDERS.contains(&key) {
+            anyhow::bail!("unknown placeholder {{{key}}} in {template:?}");
+        }
+        let value = values
+            .iter()
+            .find(|(name, _)| *name == key)
+            .map_or("", |(_, value)| value);
+        name.push_str(&sanitize_name_part(value));
+        rest = &after[end + 1..];
+    }
+    name.push_str(rest);
+
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('.') {
+        Ok(format!("invoice{name}"))
+    } else {
+        Ok(name.to_string())
+    }
+}
+
+/// Replaces characters that are not allowed in file names on common
+/// platforms with `_`.
+fn sanitize_name_part(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|ch| match ch {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            ch if ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect()
+}
+
+/// Reduces an attachment filename from the XML to its last path component so
+/// it cannot escape the output directory. Returns `None` if nothing usable is left.
+fn sanitize_filename(name: &str) -> Option<String> {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let base: String = base
+        .chars()
+        .filter(|ch| !ch.is_control() && *ch != ':')
+        .collect();
+    let base = base.trim();
+    if base.is_empty() || base.chars().all(|ch| ch == '.') {
+        None
+    } else {
+        Some(base.to_string())
+    }
+}
+
+/// File extension for an attachment's MIME type, `bin` when unknown.
+fn extension_for_mime(mime_code: &str) -> &'static str {
+    match mime_code.to_ascii_lowercase().as_str() {
+        "application/pdf" => "pdf",
+        "text/csv" => "csv",
+        "text/plain" => "txt",
+        "text/xml" | "application/xml" => "xml",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => "xlsx",
+        "application/vnd.oasis.opendocument.spreadsheet" => "ods",
+        _ => "bin",
+    }
+}
+
+fn write_embedded_document(embedded: &EmbeddedPdf, output_path: &Path) -> Result<()> {
     fs::write(output_path, &embedded.bytes)
         .with_context(|| format!("write {}", output_path.display()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_bom_and_declared_encodings() {
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><Name>Caf\xE9 SARL</Name>";
+        assert!(
+            decode_xml(latin1)
+                .expect("decode")
+                .ends_with("<Name>Caf\u{e9} SARL</Name>")
+        );
+
+        let with_bom = b"\xEF\xBB\xBF<Name>Caf\xC3\xA9</Name>";
+        assert_eq!(
+            decode_xml(with_bom).expect("decode"),
+            "<Name>Caf\u{e9}</Name>"
+        );
+
+        assert!(decode_xml(b"<Name>Caf\xE9</Name>").is_err());
+        assert!(decode_xml(b"<?xml version='1.0' encoding='x-unknown'?><a/>").is_err());
+    }
+
+    #[test]
+    fn recognizes_gzipped_invoices() {
+        let extensions = parse_extensions("xml,ubl");
+        assert!(matches_extension(
+            Path::new("a/invoice.xml.gz"),
+            &extensions
+        ));
+        assert!(matches_extension(
+            Path::new("a/invoice.UBL.GZ"),
+            &extensions
+        ));
+        assert!(!matches_extension(Path::new("a/notes.txt.gz"), &extensions));
+        assert!(!matches_extension(Path::new("a/archive.gz"), &extensions));
+        assert_eq!(source_stem(Path::new("a/invoice.xml.gz")), "invoice");
+        assert_eq!(source_stem(Path::new("a/invoice.xml")), "invoice");
+    }
+
+    #[test]
+    fn fills_name_template_with_sanitized_values() {
+        let values = [
+            ("invoice_number", "INV/7"),
+            ("supplier_vat", ""),
+            ("issue_date", "2024-01-01"),
+        ];
+        let name = fill_name_template("{supplier_vat}_{invoice_number}_{issue_date}.pdf", &values)
+            .expect("fill template");
+        assert_eq!(name, "_INV_7_2024-01-01.pdf");
+        assert_eq!(
+            fill_name_template("{currency}.pdf", &values).expect("fill template"),
+            "invoice.pdf"
+        );
+        assert!(fill_name_template("{total}.pdf", &values).is_err());
+        assert!(fill_name_template("{stem.pdf", &values).is_err());
+    }
+
+    #[test]
+    fn sanitizes_embedded_filenames() {
+        assert_eq!(sanitize_filename("orig.pdf").as_deref(), Some("orig.pdf"));
+        assert_eq!(
+            sanitize_filename("../../etc/x.pdf").as_deref(),
+            Some("x.pdf")
+        );
+        assert_eq!(
+            sanitize_filename("/abs/path/y.pdf").as_deref(),
+            Some("y.pdf")
+        );
+        assert_eq!(
+            sanitize_filename("C:\\docs\\z.pdf").as_deref(),
+            Some("z.pdf")
+        );
+        assert_eq!(sanitize_filename(".."), None);
+        assert_eq!(sanitize_filename("dir/"), None);
+    }
+}
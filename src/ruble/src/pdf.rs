@@ -0,0 +1,1975 @@
+//! PDF rendering of parsed invoices.
+
+use std::cell::Cell;
+use std::io::{BufWriter, Cursor, Write};
+#[cfg(feature = "std-fs")]
+use std::path::Path;
+
+use printpdf::image_crate::{self, DynamicImage, ImageBuffer, Rgb as RgbPixel};
+use printpdf::{
+    Actions, BorderArray, BuiltinFont, Color, Image, ImageTransform, IndirectFontRef, Line,
+    LinkAnnotation, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point, Rect, Rgb,
+};
+use qrcode::{EcLevel, QrCode};
+use unicode_bidi::BidiInfo;
+
+use crate::format::parse_amount;
+use crate::validate::iban_display;
+use crate::{
+    InvoiceData, InvoiceError, InvoiceLine, address_lines, adjustment_label, distinct_legal_name,
+    format_amount_with, format_amount_with_symbol, format_date, is_web_link,
+};
+
+/// Distance from the top page edge to the first text baseline, in mm.
+const TOP_MARGIN: f64 = 13.0;
+const PAGE_BOTTOM: f64 = 20.0;
+/// Content width of A4 with the default margins; column offsets are given
+/// for this width and scaled to the actual one.
+const REFERENCE_WIDTH: f64 = 174.0;
+const PT_TO_MM: f64 = 0.3528;
+const QR_SIZE: f64 = 28.0;
+const LOGO_WIDTH: f64 = 40.0;
+const LOGO_HEIGHT: f64 = 18.0;
+const LOGO_MAX_PIXELS: u32 = 4096;
+
+/// A named set of colours for the generated PDF, see
+/// [`InvoiceStyle::from_theme`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Theme {
+    /// Navy title on white paper, the default look.
+    #[default]
+    Classic,
+    /// Charcoal text throughout with faint rules and no colour accent.
+    Minimal,
+    /// Light text on a dark page, for invoices mostly read on screen.
+    Dark,
+}
+
+/// Paper size of the generated PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PageSize {
+    /// 210 × 297 mm.
+    #[default]
+    A4,
+    /// US Letter, 8.5 × 11 in (215.9 × 279.4 mm).
+    Letter,
+    Custom {
+        width_mm: f64,
+        height_mm: f64,
+    },
+}
+
+impl PageSize {
+    /// Width and height in mm.
+    pub fn dimensions(self) -> (f64, f64) {
+        match self {
+            Self::A4 => (210.0, 297.0),
+            Self::Letter => (215.9, 279.4),
+            Self::Custom {
+                width_mm,
+                height_mm,
+            } => (width_mm, height_mm),
+        }
+    }
+}
+
+/// Visual settings for the generated PDF.
+#[derive(Debug, Clone)]
+pub struct InvoiceStyle {
+    /// Paper size; margins and columns are laid out relative to it.
+    pub page_size: PageSize,
+    /// Title colour as RGB components in `0.0..=1.0`.
+    pub accent_color: (f32, f32, f32),
+    /// Colour of body text and amounts.
+    pub text_color: (f32, f32, f32),
+    /// Colour of the column headings of the items and VAT tables.
+    pub muted_color: (f32, f32, f32),
+    /// Colour of the rules between sections.
+    pub divider_color: (f32, f32, f32),
+    /// Fill of every page; `None` leaves the paper white. Footer text and
+    /// zebra stripes are blended from it and [`InvoiceStyle::text_color`].
+    pub background_color: Option<(f32, f32, f32)>,
+    /// Distance from the left page edge to the content, in mm.
+    pub left_margin: f64,
+    /// Distance from the right page edge to the content, in mm.
+    pub right_margin: f64,
+    /// Body text size in points; headings and table text scale from it.
+    pub base_font_size: f64,
+    /// Draw an EPC (SEPA credit transfer) QR code when the invoice has an IBAN.
+    pub payment_qr: bool,
+    /// PNG or JPEG bytes drawn in the top-right corner. A logo that cannot be
+    /// decoded is skipped; use [`validate_logo`] to check it up front.
+    pub logo: Option<Vec<u8>>,
+    /// TrueType/OpenType font embedded for body text instead of the built-in
+    /// Helvetica, which only covers WinAnsi (Western European) characters.
+    pub font: Option<Vec<u8>>,
+    /// Font for headings and totals; falls back to [`InvoiceStyle::font`].
+    pub font_bold: Option<Vec<u8>>,
+    /// Generation date printed in every page footer; `None` uses today's UTC date.
+    pub generated_on: Option<String>,
+    /// Pattern for the header dates, see [`format_date`]; `None` prints them
+    /// as stored in the XML.
+    pub date_format: Option<String>,
+    /// Language of month names in [`InvoiceStyle::date_format`], which also
+    /// places currency symbols; `None` is English.
+    pub date_locale: Option<String>,
+    /// Most decimals shown for unit prices; trailing zeros past two are dropped.
+    pub price_decimals: usize,
+    /// Decimals shown for line totals, totals and the VAT breakdown.
+    pub amount_decimals: usize,
+    /// Print amounts with the currency symbol (`€12.00`) instead of the ISO
+    /// code (`EUR 12.00`) when the currency has one, see [`currency_symbol`].
+    ///
+    /// [`currency_symbol`]: crate::currency_symbol
+    pub use_currency_symbol: bool,
+    /// Fail with [`InvoiceError::UnsupportedGlyph`] when text contains a
+    /// character the font cannot draw; otherwise it is printed as `?`.
+    pub strict_fonts: bool,
+    /// Shade every other item row light gray to guide the eye on long tables.
+    pub zebra: bool,
+    /// Lay out party names and addresses written in Arabic or Hebrew right
+    /// to left: reorder them into visual order and align them to the right
+    /// of their column. Needs an embedded [`InvoiceStyle::font`] covering
+    /// the script; Arabic letters are not joined into their connected forms.
+    pub rtl: bool,
+}
+
+impl Default for InvoiceStyle {
+    fn default() -> Self {
+        Self {
+            page_size: PageSize::A4,
+            accent_color: (0.14, 0.22, 0.33),
+            text_color: (0.0, 0.0, 0.0),
+            muted_color: (0.35, 0.35, 0.35),
+            divider_color: (0.75, 0.75, 0.75),
+            background_color: None,
+            left_margin: 18.0,
+            right_margin: 18.0,
+            base_font_size: 10.0,
+            payment_qr: false,
+            logo: None,
+            font: None,
+            font_bold: None,
+            generated_on: None,
+            date_format: None,
+            date_locale: None,
+            price_decimals: 4,
+            amount_decimals: 2,
+            use_currency_symbol: false,
+            strict_fonts: false,
+            zebra: false,
+            rtl: false,
+        }
+    }
+}
+
+impl InvoiceStyle {
+    /// The default style with the colours of `theme`; [`Theme::Classic`] is
+    /// the same as [`InvoiceStyle::default`].
+    pub fn from_theme(theme: Theme) -> Self {
+        let style = Self::default();
+        match theme {
+            Theme::Classic => style,
+            Theme::Minimal => Self {
+                accent_color: (0.2, 0.2, 0.2),
+                text_color: (0.2, 0.2, 0.2),
+                muted_color: (0.5, 0.5, 0.5),
+                divider_color: (0.88, 0.88, 0.88),
+                ..style
+            },
+            Theme::Dark => Self {
+                accent_color: (0.45, 0.68, 0.95),
+                text_color: (0.92, 0.92, 0.92),
+                muted_color: (0.65, 0.65, 0.65),
+                divider_color: (0.35, 0.37, 0.4),
+                background_color: Some((0.12, 0.13, 0.15)),
+                ..style
+            },
+        }
+    }
+}
+
+/// The style's colours, plus the ones blended from text and background.
+#[derive(Clone, Copy)]
+struct Palette {
+    text: (f32, f32, f32),
+    muted: (f32, f32, f32),
+    divider: (f32, f32, f32),
+    stripe: (f32, f32, f32),
+    footer: (f32, f32, f32),
+    background: Option<(f32, f32, f32)>,
+}
+
+impl Palette {
+    fn new(style: &InvoiceStyle) -> Self {
+        let text = style.text_color;
+        let paper = style.background_color.unwrap_or((1.0, 1.0, 1.0));
+        let blend = |amount: f32| {
+            let mix = |from: f32, to: f32| from + (to - from) * amount;
+            (
+                mix(text.0, paper.0),
+                mix(text.1, paper.1),
+                mix(text.2, paper.2),
+            )
+        };
+        Self {
+            text,
+            muted: style.muted_color,
+            divider: style.divider_color,
+            stripe: blend(0.94),
+            footer: blend(0.5),
+            background: style.background_color,
+        }
+    }
+}
+
+fn rgb((red, green, blue): (f32, f32, f32)) -> Color {
+    Color::Rgb(Rgb::new(red, green, blue, None))
+}
+
+/// Positions and number formats derived from an [`InvoiceStyle`], shared by
+/// the drawing helpers.
+struct Layout {
+    page_width: f64,
+    page_height: f64,
+    /// Baseline of the first line on every page.
+    top_y: f64,
+    left_x: f64,
+    right_x: f64,
+    content_right: f64,
+    qty_right: f64,
+    unit_right: f64,
+    tax_right: f64,
+    totals_label_x: f64,
+    description_width: f64,
+    line_height: f64,
+    base_font_size: f64,
+    price_decimals: usize,
+    amount_decimals: usize,
+    palette: Palette,
+    /// Locale placing currency symbols, `None` when amounts show the code.
+    symbol_locale: Option<String>,
+}
+
+impl Layout {
+    fn new(style: &InvoiceStyle) -> Self {
+        let (page_width, page_height) = style.page_size.dimensions();
+        let left_x = style.left_margin;
+        let content_right = page_width - style.right_margin;
+        // Offsets from the right edge, scaled so the table keeps its proportions.
+        let column = |offset: f64| offset * (content_right - left_x) / REFERENCE_WIDTH;
+        let qty_right = content_right - column(70.0);
+        Self {
+            page_width,
+            page_height,
+            top_y: page_height - TOP_MARGIN,
+            left_x,
+            right_x: (left_x + content_right) / 2.0 + 5.0,
+            content_right,
+            qty_right,
+            unit_right: content_right - column(42.0),
+            tax_right: content_right - column(28.0),
+            totals_label_x: content_right - column(62.0),
+            description_width: qty_right - column(18.0) - left_x,
+            line_height: style.base_font_size * 0.65,
+            base_font_size: style.base_font_size,
+            price_decimals: style.price_decimals,
+            amount_decimals: style.amount_decimals,
+            palette: Palette::new(style),
+            symbol_locale: style.use_currency_symbol.then(|| {
+                style
+                    .date_locale
+                    .clone()
+                    .unwrap_or_else(|| "en".to_string())
+            }),
+        }
+    }
+
+    /// A unit price with up to [`InvoiceStyle::price_decimals`] decimals.
+    fn price(&self, raw: &str, currency: &str) -> String {
+        let min = self.price_decimals.min(2);
+        self.format(raw, currency, min, self.price_decimals)
+    }
+
+    /// Any other amount, at [`InvoiceStyle::amount_decimals`] decimals.
+    fn amount(&self, raw: &str, currency: &str) -> String {
+        self.format(raw, currency, self.amount_decimals, self.amount_decimals)
+    }
+
+    fn format(&self, raw: &str, currency: &str, min: usize, max: usize) -> String {
+        match &self.symbol_locale {
+            Some(locale) => format_amount_with_symbol(raw, currency, min, max, locale),
+            None => format_amount_with(raw, currency, min, max),
+        }
+    }
+
+    /// Font size scaled relative to the body text size.
+    fn size(&self, factor: f64) -> f64 {
+        self.base_font_size * factor
+    }
+}
+
+#[cfg(feature = "std-fs")]
+pub fn create_invoice_pdf(data: &InvoiceData, output_file: &Path) -> Result<(), InvoiceError> {
+    create_invoice_pdf_styled(data, &InvoiceStyle::default(), output_file)
+}
+
+#[cfg(feature = "std-fs")]
+pub fn create_invoice_pdf_styled(
+    data: &InvoiceData,
+    style: &InvoiceStyle,
+    output_file: &Path,
+) -> Result<(), InvoiceError> {
+    write_invoice_pdf_styled(data, style, std::fs::File::create(output_file)?)
+}
+
+pub fn create_invoice_pdf_bytes(data: &InvoiceData) -> Result<Vec<u8>, InvoiceError> {
+    create_invoice_pdf_bytes_styled(data, &InvoiceStyle::default())
+}
+
+pub fn create_invoice_pdf_bytes_styled(
+    data: &InvoiceData,
+    style: &InvoiceStyle,
+) -> Result<Vec<u8>, InvoiceError> {
+    Ok(render_pdf(data, style)?.save_to_bytes()?)
+}
+
+pub fn write_invoice_pdf<W: Write>(data: &InvoiceData, writer: W) -> Result<(), InvoiceError> {
+    write_invoice_pdf_styled(data, &InvoiceStyle::default(), writer)
+}
+
+/// Renders the invoice into `writer`, e.g. a socket or an upload stream,
+/// instead of a file or a returned buffer.
+pub fn write_invoice_pdf_styled<W: Write>(
+    data: &InvoiceData,
+    style: &InvoiceStyle,
+    writer: W,
+) -> Result<(), InvoiceError> {
+    let mut writer = BufWriter::new(writer);
+    render_pdf(data, style)?.save(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn render_pdf(
+    data: &InvoiceData,
+    style: &InvoiceStyle,
+) -> Result<PdfDocumentReference, InvoiceError> {
+    let layout = Layout::new(style);
+    let (doc, page1, layer1) = PdfDocument::new(
+        "Invoice",
+        Mm(layout.page_width as f32),
+        Mm(layout.page_height as f32),
+        "Layer 1",
+    );
+    let font = Font::load(&doc, style.font.as_deref(), BuiltinFont::Helvetica)?;
+    let font_bold = Font::load(
+        &doc,
+        style.font_bold.as_deref().or(style.font.as_deref()),
+        BuiltinFont::HelveticaBold,
+    )?;
+    let mut layer = doc.get_page(page1).get_layer(layer1);
+    let mut pages = vec![layer.clone()];
+
+    let Layout {
+        left_x,
+        right_x,
+        content_right,
+        line_height,
+        ..
+    } = layout;
+    let body = layout.size(1.0);
+    let small = layout.size(0.95);
+    let heading = layout.size(1.1);
+    let mut y = layout.top_y;
+
+    let palette = layout.palette;
+    prepare_page(&layer, &layout);
+    layer.set_fill_color(rgb(style.accent_color));
+    let title = data.title();
+    let title_size = layout.size(2.2);
+    write_text(&layer, &font_bold, title_size, left_x, y, title);
+    if data.is_corrected() {
+        let x = left_x + font_bold.width(title, title_size) + 4.0;
+        layer.set_fill_color(rgb((0.7, 0.1, 0.1)));
+        write_text(&layer, &font_bold, layout.size(1.2), x, y, "CORRECTED");
+    }
+    layer.set_fill_color(rgb(palette.text));
+    let mut header_bottom = y - 10.0;
+    let logo = style
+        .logo
+        .as_deref()
+        .and_then(|bytes| decode_logo(bytes).ok());
+    let logo_top = y + 6.0;
+    if let Some(logo) = logo {
+        let bottom = draw_logo(&layer, logo, content_right, logo_top);
+        header_bottom = header_bottom.min(bottom - 6.0);
+    }
+    if style.payment_qr
+        && let Some(payload) = epc_qr_payload(data)
+    {
+        if style.logo.is_some() && header_bottom < y - 10.0 {
+            // Sit to the left of the logo and keep the details below both.
+            let x = content_right - LOGO_WIDTH - 4.0 - QR_SIZE;
+            draw_qr_code(&layer, &palette, &payload, x, logo_top, QR_SIZE)?;
+            header_bottom = header_bottom.min(logo_top - QR_SIZE - 6.0);
+        } else {
+            let x = content_right - QR_SIZE;
+            draw_qr_code(&layer, &palette, &payload, x, y + 2.0, QR_SIZE)?;
+        }
+    }
+    y = header_bottom;
+
+    write_text(&layer, &font_bold, body, left_x, y, "Invoice details");
+    y -= 6.0;
+    let optional =
+        |label: &str, value: &str| (!value.is_empty()).then(|| format!("{label}: {value}"));
+    let date = |raw: &str| match &style.date_format {
+        Some(pattern) => format_date(raw, pattern, style.date_locale.as_deref().unwrap_or("en")),
+        None => raw.to_string(),
+    };
+    let details_left: Vec<String> = [
+        Some(format!("Invoice Number: {}", data.invoice_number)),
+        optional("Order Reference", &data.order_reference),
+        optional("Buyer Reference", &data.buyer_reference),
+        optional("Relates to invoice", &data.preceding_invoice_reference),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let mut details_right: Vec<String> = [
+        Some(format!("Issue Date: {}", date(&data.issue_date))),
+        optional("Delivery Date", &date(&data.delivery_date)),
+        optional("Tax Point Date", &date(data.distinct_tax_point_date())),
+        (!data.period_start.is_empty() && !data.period_end.is_empty()).then(|| {
+            format!(
+                "Period: {} – {}",
+                date(&data.period_start),
+                date(&data.period_end)
+            )
+        }),
+        optional("Due Date", &date(&data.due_date)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if let Some(terms) = optional("Payment Terms", &data.payment_terms) {
+        details_right.extend(wrap_text(&font, &terms, body, content_right - right_x));
+    }
+    // Two rows are always reserved so the header keeps its height.
+    let detail_rows = details_left.len().max(details_right.len()).max(2);
+    for row in 0..detail_rows {
+        let row_y = y - row as f64 * line_height;
+        if let Some(text) = details_left.get(row) {
+            write_text(&layer, &font, body, left_x, row_y, text);
+        }
+        if let Some(text) = details_right.get(row) {
+            write_text(&layer, &font, body, right_x, row_y, text);
+        }
+    }
+    y -= (detail_rows - 1) as f64 * line_height;
+
+    y -= 8.0;
+    draw_divider(&layer, &layout, y);
+    y -= 7.0;
+
+    write_text(&layer, &font_bold, heading, left_x, y, "Supplier");
+    write_text(&layer, &font_bold, heading, right_x, y, "Customer");
+    y -= line_height;
+    let supplier_column = (left_x, right_x - 10.0);
+    let customer_column = (right_x, content_right);
+    let party = |size, column, y, text: &str| {
+        write_party_text(&layer, &font, size, column, y, text, style.rtl);
+    };
+    party(body, supplier_column, y, &data.supplier_name);
+    party(body, customer_column, y, &data.customer_name);
+    y -= line_height;
+    let supplier_legal = distinct_legal_name(&data.supplier_name, &data.supplier_legal_name);
+    let customer_legal = distinct_legal_name(&data.customer_name, &data.customer_legal_name);
+    if supplier_legal.is_some() || customer_legal.is_some() {
+        if let Some(name) = supplier_legal {
+            party(small, supplier_column, y, name);
+        }
+        if let Some(name) = customer_legal {
+            party(small, customer_column, y, name);
+        }
+        y -= line_height;
+    }
+    let supplier_lines = address_lines(&data.supplier_address);
+    let customer_lines = address_lines(&data.customer_address);
+    for row in 0..supplier_lines.len().max(customer_lines.len()) {
+        if let Some(line) = supplier_lines.get(row) {
+            party(small, supplier_column, y, line);
+        }
+        if let Some(line) = customer_lines.get(row) {
+            party(small, customer_column, y, line);
+        }
+        y -= line_height;
+    }
+    if !data.supplier_vat.is_empty() || !data.customer_vat.is_empty() {
+        write_text(
+            &layer,
+            &font,
+            small,
+            left_x,
+            y,
+            &format!("VAT: {}", data.supplier_vat),
+        );
+        write_text(
+            &layer,
+            &font,
+            small,
+            right_x,
+            y,
+            &format!("VAT: {}", data.customer_vat),
+        );
+        y -= line_height;
+    }
+    if let Some(contact) = &data.supplier_contact {
+        let contact = format!("Contact: {}", contact.summary());
+        for line in wrap_text(&font, &contact, small, right_x - left_x) {
+            write_text(&layer, &font, small, left_x, y, &line);
+            y -= line_height;
+        }
+    }
+
+    if !data.payment_iban.is_empty() || !data.payment_bic.is_empty() {
+        y -= 6.0;
+        draw_divider(&layer, &layout, y);
+        y -= 7.0;
+        write_text(&layer, &font_bold, heading, left_x, y, "Payment");
+        y -= line_height;
+        if !data.payment_iban.is_empty() {
+            write_text(
+                &layer,
+                &font,
+                small,
+                left_x,
+                y,
+                &format!("IBAN: {}", iban_display(&data.payment_iban)),
+            );
+        }
+        if !data.payment_bic.is_empty() {
+            write_text(
+                &layer,
+                &font,
+                small,
+                right_x,
+                y,
+                &format!("BIC: {}", data.payment_bic),
+            );
+        }
+        y -= line_height;
+    }
+
+    y -= 6.0;
+    draw_divider(&layer, &layout, y);
+    y -= 7.0;
+
+    write_text(&layer, &font_bold, heading, left_x, y, "Items");
+    y -= 6.0;
+    y = draw_items_header(&layer, &font_bold, &layout, y);
+
+    for (index, line) in data.lines.iter().enumerate() {
+        let rows = item_rows(&font, &font_bold, &layout, line);
+        let row_height = rows.len() as f64 * line_height;
+        if y - row_height + line_height < PAGE_BOTTOM {
+            layer = add_page(&doc, &layout);
+            pages.push(layer.clone());
+            y = draw_items_header(&layer, &font_bold, &layout, layout.top_y);
+        }
+        if style.zebra && index % 2 == 1 {
+            draw_stripe(&layer, &layout, y, row_height);
+        }
+        y = draw_line_item(&layer, &font, &layout, &data.currency, line, rows, y);
+    }
+
+    // Keep the whole totals block together on the last page.
+    let table = layout.size(0.85);
+    let exemptions: Vec<Vec<String>> = data
+        .tax_breakdown
+        .iter()
+        .map(|subtotal| match subtotal.exemption() {
+            "" => Vec::new(),
+            reason => wrap_text(
+                &font,
+                &format!("— {reason}"),
+                table,
+                content_right - layout.totals_label_x,
+            ),
+        })
+        .collect();
+    let breakdown_rows = match data.tax_breakdown.len() {
+        0 => 0,
+        rows => rows + 1 + exemptions.iter().map(Vec::len).sum::<usize>(),
+    };
+    let due_rows = if data.has_separate_amount_due() {
+        1 + usize::from(data.has_prepaid()) + usize::from(data.has_rounding())
+    } else {
+        0
+    };
+    let totals_height = 4.0
+        + 7.0
+        + (2 + data.allowances_charges.len() + breakdown_rows + due_rows) as f64 * line_height;
+    if y - totals_height < PAGE_BOTTOM {
+        layer = add_page(&doc, &layout);
+        pages.push(layer.clone());
+        y = layout.top_y;
+    }
+    y -= 4.0;
+    draw_divider(&layer, &layout, y);
+    y -= 7.0;
+    let amount = |raw: &str| layout.amount(raw, &data.currency);
+    write_total_row(
+        &layer,
+        &font,
+        &layout,
+        body,
+        y,
+        "Subtotal:",
+        &amount(&data.subtotal),
+    );
+    y -= line_height;
+    for entry in &data.allowances_charges {
+        let (label, sign) = if entry.is_charge {
+            ("Charge", "")
+        } else {
+            ("Discount", "-")
+        };
+        let label = if entry.reason.is_empty() {
+            label
+        } else {
+            &entry.reason
+        };
+        write_total_row(
+            &layer,
+            &font,
+            &layout,
+            body,
+            y,
+            &format!("{label}:"),
+            &amount(&format!("{sign}{}", entry.amount)),
+        );
+        y -= line_height;
+    }
+    write_total_row(
+        &layer,
+        &font,
+        &layout,
+        body,
+        y,
+        "VAT:",
+        &amount(&data.tax_total),
+    );
+    y -= line_height;
+    if !data.tax_breakdown.is_empty() {
+        layer.set_fill_color(rgb(palette.muted));
+        write_text(&layer, &font_bold, table, layout.totals_label_x, y, "Rate");
+        write_text_right_aligned(&layer, &font_bold, table, layout.tax_right, y, "Taxable");
+        write_text_right_aligned(&layer, &font_bold, table, content_right, y, "VAT");
+        y -= line_height;
+        for (subtotal, exemption) in data.tax_breakdown.iter().zip(&exemptions) {
+            let rate = match (subtotal.percent.is_empty(), subtotal.category.is_empty()) {
+                (false, false) => format!("{}% ({})", subtotal.percent, subtotal.category),
+                (false, true) => format!("{}%", subtotal.percent),
+                (true, _) => subtotal.category.clone(),
+            };
+            write_text(&layer, &font, table, layout.totals_label_x, y, &rate);
+            write_text_right_aligned(
+                &layer,
+                &font,
+                table,
+                layout.tax_right,
+                y,
+                &amount(&subtotal.taxable_amount),
+            );
+            write_text_right_aligned(
+                &layer,
+                &font,
+                table,
+                content_right,
+                y,
+                &amount(&subtotal.tax_amount),
+            );
+            y -= line_height;
+            for text in exemption {
+                write_text(&layer, &font, table, layout.totals_label_x, y, text);
+                y -= line_height;
+            }
+        }
+        layer.set_fill_color(rgb(palette.text));
+    }
+    if data.has_separate_amount_due() {
+        write_total_row(
+            &layer,
+            &font,
+            &layout,
+            body,
+            y,
+            "Total:",
+            &amount(&data.total),
+        );
+        y -= line_height;
+        if data.has_prepaid() {
+            write_total_row(
+                &layer,
+                &font,
+                &layout,
+                body,
+                y,
+                "Prepaid:",
+                &amount(&format!("-{}", data.prepaid_amount)),
+            );
+            y -= line_height;
+        }
+        if data.has_rounding() {
+            write_total_row(
+                &layer,
+                &font,
+                &layout,
+                body,
+                y,
+                "Rounding:",
+                &amount(&data.rounding_amount),
+            );
+            y -= line_height;
+        }
+        write_total_row(
+            &layer,
+            &font_bold,
+            &layout,
+            layout.size(1.2),
+            y,
+            "Amount due:",
+            &amount(&data.amount_due()),
+        );
+    } else {
+        write_total_row(
+            &layer,
+            &font_bold,
+            &layout,
+            layout.size(1.2),
+            y,
+            "Total:",
+            &amount(&data.total),
+        );
+    }
+
+    if !data.notes.is_empty() {
+        let size = layout.size(0.9);
+        let width = content_right - left_x;
+        let rows: Vec<Vec<String>> = data
+            .notes
+            .iter()
+            .map(|note| wrap_text(&font, note, size, width))
+            .collect();
+        y -= line_height + 6.0;
+        // Keep the heading together with the first note row.
+        if y - line_height < PAGE_BOTTOM {
+            layer = add_page(&doc, &layout);
+            pages.push(layer.clone());
+            y = layout.top_y;
+        }
+        write_text(&layer, &font_bold, body, left_x, y, "Notes");
+        y -= 6.0;
+        for (index, note) in rows.iter().enumerate() {
+            if index > 0 {
+                y -= 2.0;
+            }
+            for text in note {
+                if y < PAGE_BOTTOM {
+                    layer = add_page(&doc, &layout);
+                    pages.push(layer.clone());
+                    y = layout.top_y;
+                }
+                write_text(&layer, &font, size, left_x, y, text);
+                y -= line_height;
+            }
+        }
+    }
+
+    if !data.external_references.is_empty() {
+        let size = layout.size(0.9);
+        let width = content_right - left_x;
+        y -= if data.notes.is_empty() {
+            line_height + 6.0
+        } else {
+            6.0
+        };
+        if y - line_height < PAGE_BOTTOM {
+            layer = add_page(&doc, &layout);
+            pages.push(layer.clone());
+            y = layout.top_y;
+        }
+        write_text(&layer, &font_bold, body, left_x, y, "References");
+        y -= 6.0;
+        for uri in &data.external_references {
+            // Only web links are clickable; other schemes are shown as text.
+            let link = is_web_link(uri);
+            let color = if link {
+                style.accent_color
+            } else {
+                palette.text
+            };
+            layer.set_fill_color(rgb(color));
+            for text in wrap_text(&font, uri, size, width) {
+                if y < PAGE_BOTTOM {
+                    layer = add_page(&doc, &layout);
+                    pages.push(layer.clone());
+                    layer.set_fill_color(rgb(color));
+                    y = layout.top_y;
+                }
+                write_text(&layer, &font, size, left_x, y, &text);
+                if link {
+                    let right = left_x + font.width(&text, size);
+                    add_link(&layer, uri.trim(), left_x, y, right, size);
+                }
+                y -= line_height;
+            }
+        }
+        layer.set_fill_color(rgb(palette.text));
+    }
+
+    let generated_on = style.generated_on.clone().or_else(today_utc);
+    for (index, page) in pages.iter().enumerate() {
+        let mut footer = format!("Page {} of {}", index + 1, pages.len());
+        if let Some(date) = &generated_on {
+            footer.push_str(&format!(" - generated {date}"));
+        }
+        let size = layout.size(0.8);
+        let x = (left_x + content_right - font.width(&footer, size)) / 2.0;
+        page.set_fill_color(rgb(palette.footer));
+        write_text(page, &font, size, x, PAGE_BOTTOM - 8.0, &footer);
+    }
+
+    if let Some(ch) = font.missing.get().or(font_bold.missing.get()) {
+        if style.strict_fonts {
+            return Err(InvoiceError::UnsupportedGlyph(ch));
+        }
+        tracing::warn!(character = %ch.escape_unicode(), "character not in the font, printed as ?");
+    }
+    Ok(doc)
+}
+
+/// Today's date in UTC as YYYY-MM-DD, or `None` on `wasm32-unknown-unknown`,
+/// which has no clock (`SystemTime::now` panics there).
+fn today_utc() -> Option<String> {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        return None;
+    }
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    // Civil-from-days conversion (Howard Hinnant's algorithm).
+    let days = (seconds / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+/// Checks that `bytes` hold a TrueType or OpenType font usable as
+/// [`InvoiceStyle::font`].
+pub fn validate_font(bytes: &[u8]) -> Result<(), InvoiceError> {
+    ttf_parser::Face::parse(bytes, 0)?;
+    Ok(())
+}
+
+/// A font added to the document, plus the metrics needed to measure text.
+struct Font<'a> {
+    reference: IndirectFontRef,
+    /// Parsed outlines of an embedded font; `None` for built-in Helvetica.
+    face: Option<ttf_parser::Face<'a>>,
+    /// First character written that the font could not encode.
+    missing: Cell<Option<char>>,
+}
+
+impl<'a> Font<'a> {
+    /// Embeds `bytes` when given, otherwise uses the `builtin` font.
+    fn load(
+        doc: &PdfDocumentReference,
+        bytes: Option<&'a [u8]>,
+        builtin: BuiltinFont,
+    ) -> Result<Self, InvoiceError> {
+        match bytes {
+            Some(bytes) => Ok(Self {
+                face: Some(ttf_parser::Face::parse(bytes, 0)?),
+                reference: doc.add_external_font(bytes)?,
+                missing: Cell::new(None),
+            }),
+            None => Ok(Self {
+                reference: doc.add_builtin_font(builtin)?,
+                face: None,
+                missing: Cell::new(None),
+            }),
+        }
+    }
+
+    /// Whether the font can draw `ch`: a glyph in an embedded font, or a
+    /// WinAnsi code point for built-in Helvetica.
+    fn encodes(&self, ch: char) -> bool {
+        match &self.face {
+            Some(face) => face.glyph_index(ch).is_some(),
+            None => is_win_ansi(ch),
+        }
+    }
+
+    /// Advance width of `text` in mm.
+    fn width(&self, text: &str, size: f64) -> f64 {
+        let Some(face) = &self.face else {
+            return text_width(text, size);
+        };
+        let units_per_em = f64::from(face.units_per_em());
+        let units: f64 = text
+            .chars()
+            .map(|ch| {
+                let glyph = face.glyph_index(ch).unwrap_or_default();
+                f64::from(face.glyph_hor_advance(glyph).unwrap_or_default())
+            })
+            .sum();
+        units / units_per_em * size * PT_TO_MM
+    }
+}
+
+/// Checks that `bytes` hold a PNG or JPEG logo that [`InvoiceStyle::logo`]
+/// can render, rejecting images wider or taller than 4096 pixels.
+pub fn validate_logo(bytes: &[u8]) -> Result<(), InvoiceError> {
+    decode_logo(bytes).map(|_| ())
+}
+
+fn decode_logo(bytes: &[u8]) -> Result<DynamicImage, InvoiceError> {
+    let mut limits = image_crate::io::Limits::default();
+    limits.max_image_width = Some(LOGO_MAX_PIXELS);
+    limits.max_image_height = Some(LOGO_MAX_PIXELS);
+    let mut reader = image_crate::io::Reader::new(Cursor::new(bytes)).with_guessed_format()?;
+    reader.limits(limits);
+    let image = reader.decode()?;
+
+    // PDF images here carry no alpha channel, so flatten transparency onto white.
+    let rgba = image.to_rgba8();
+    let flattened = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [red, green, blue, alpha] = rgba.get_pixel(x, y).0;
+        let blend = |channel: u8| {
+            let alpha = u16::from(alpha);
+            ((u16::from(channel) * alpha + 255 * (255 - alpha)) / 255) as u8
+        };
+        RgbPixel([blend(red), blend(green), blend(blue)])
+    });
+    Ok(DynamicImage::ImageRgb8(flattened))
+}
+
+/// Draws `logo` scaled to fit the logo box, right-aligned at `right` with its
+/// top at `top`, and returns the y position of its bottom edge.
+fn draw_logo(layer: &PdfLayerReference, logo: DynamicImage, right: f64, top: f64) -> f64 {
+    const DPI: f64 = 300.0;
+    let native_width = f64::from(logo.width()) / DPI * 25.4;
+    let native_height = f64::from(logo.height()) / DPI * 25.4;
+    let scale = (LOGO_WIDTH / native_width).min(LOGO_HEIGHT / native_height);
+    let width = native_width * scale;
+    let height = native_height * scale;
+    Image::from_dynamic_image(&logo).add_to_layer(
+        layer.clone(),
+        ImageTransform {
+            translate_x: Some(Mm((right - width) as f32)),
+            translate_y: Some(Mm((top - height) as f32)),
+            scale_x: Some(scale as f32),
+            scale_y: Some(scale as f32),
+            dpi: Some(DPI as f32),
+            ..ImageTransform::default()
+        },
+    );
+    top - height
+}
+
+/// Builds an EPC069-12 (version 002) SEPA credit transfer payload, or `None`
+/// when the invoice has no IBAN. The amount is only included for EUR invoices.
+fn epc_qr_payload(data: &InvoiceData) -> Option<String> {
+    if data.payment_iban.is_empty() {
+        return None;
+    }
+    let amount = match parse_amount(&data.amount_due()) {
+        Some(value) if data.currency == "EUR" && value > 0.0 => format!("EUR{value:.2}"),
+        _ => String::new(),
+    };
+    let name: String = data.supplier_name.chars().take(70).collect();
+    let iban: String = data.payment_iban.split_whitespace().collect();
+    let remittance: String = data.invoice_number.chars().take(140).collect();
+    Some(
+        [
+            "BCD",
+            "002",
+            "1",
+            "SCT",
+            &data.payment_bic,
+            &name,
+            &iban,
+            &amount,
+            "",
+            "",
+            &remittance,
+        ]
+        .join("\n"),
+    )
+}
+
+/// Draws `payload` as a QR code whose top-left corner sits at (`x`, `top`).
+/// On a coloured page it gets a white backing so scanners still find it.
+fn draw_qr_code(
+    layer: &PdfLayerReference,
+    palette: &Palette,
+    payload: &str,
+    x: f64,
+    top: f64,
+    size: f64,
+) -> Result<(), InvoiceError> {
+    let code = QrCode::with_error_correction_level(payload.as_bytes(), EcLevel::M)?;
+    let width = code.width();
+    let module = size / width as f64;
+    let colors = code.to_colors();
+    if palette.background.is_some() {
+        let margin = module * 2.0;
+        layer.set_fill_color(rgb((1.0, 1.0, 1.0)));
+        layer.add_rect(Rect::new(
+            Mm((x - margin) as f32),
+            Mm((top - size - margin) as f32),
+            Mm((x + size + margin) as f32),
+            Mm((top + margin) as f32),
+        ));
+    }
+    layer.set_fill_color(rgb((0.0, 0.0, 0.0)));
+    for (row, modules) in colors.chunks(width).enumerate() {
+        let y_top = top - row as f64 * module;
+        // Merge horizontal runs of dark modules into one rectangle each.
+        let mut col = 0;
+        while col < width {
+            if modules[col] != qrcode::Color::Dark {
+                col += 1;
+                continue;
+            }
+            let start = col;
+            while col < width && modules[col] == qrcode::Color::Dark {
+                col += 1;
+            }
+            layer.add_rect(Rect::new(
+                Mm((x + start as f64 * module) as f32),
+                Mm((y_top - module) as f32),
+                Mm((x + col as f64 * module) as f32),
+                Mm(y_top as f32),
+            ));
+        }
+    }
+    layer.set_fill_color(rgb(palette.text));
+    Ok(())
+}
+
+fn add_page(doc: &PdfDocumentReference, layout: &Layout) -> PdfLayerReference {
+    let (page, layer) = doc.add_page(
+        Mm(layout.page_width as f32),
+        Mm(layout.page_height as f32),
+        "Layer 1",
+    );
+    let layer = doc.get_page(page).get_layer(layer);
+    prepare_page(&layer, layout);
+    layer
+}
+
+/// Paints the page background, if any, and selects the text colour.
+fn prepare_page(layer: &PdfLayerReference, layout: &Layout) {
+    if let Some(background) = layout.palette.background {
+        layer.set_fill_color(rgb(background));
+        layer.add_rect(Rect::new(
+            Mm(0.0),
+            Mm(0.0),
+            Mm(layout.page_width as f32),
+            Mm(layout.page_height as f32),
+        ));
+    }
+    layer.set_fill_color(rgb(layout.palette.text));
+}
+
+/// Draws the items column headings and returns the y position of the first row.
+fn draw_items_header(
+    layer: &PdfLayerReference,
+    font_bold: &Font,
+    layout: &Layout,
+    mut y: f64,
+) -> f64 {
+    let size = layout.size(0.95);
+    layer.set_fill_color(rgb(layout.palette.muted));
+    write_text(layer, font_bold, size, layout.left_x, y, "Description");
+    write_text_right_aligned(layer, font_bold, size, layout.qty_right, y, "Qty");
+    write_text_right_aligned(layer, font_bold, size, layout.unit_right, y, "Unit");
+    write_text_right_aligned(layer, font_bold, size, layout.tax_right, y, "VAT%");
+    write_text_right_aligned(layer, font_bold, size, layout.content_right, y, "Total");
+    layer.set_fill_color(rgb(layout.palette.text));
+    y -= 4.0;
+    draw_divider(layer, layout, y);
+    y - 6.0
+}
+
+/// Wrapped rows of the item column: the name in bold with the description
+/// underneath in smaller text, or whichever of the two is present, followed
+/// by the purchase order line when the invoice references one.
+fn item_rows<'f>(
+    font: &'f Font,
+    font_bold: &'f Font,
+    layout: &Layout,
+    line: &InvoiceLine,
+) -> Vec<(&'f Font<'f>, f64, String)> {
+    let size = layout.size(0.9);
+    let small = layout.size(0.8);
+    let width = layout.description_width;
+    let mut rows: Vec<_> = if line.name.is_empty() {
+        wrap_text(
+            font,
+            &line.with_seller_item_id(&line.description),
+            size,
+            width,
+        )
+        .into_iter()
+        .map(|text| (font, size, text))
+        .collect()
+    } else {
+        let mut rows: Vec<_> = wrap_text(
+            font_bold,
+            &line.with_seller_item_id(&line.name),
+            size,
+            width,
+        )
+        .into_iter()
+        .map(|text| (font_bold, size, text))
+        .collect();
+        if !line.description.is_empty() {
+            rows.extend(
+                wrap_text(font, &line.description, small, width)
+                    .into_iter()
+                    .map(|text| (font, small, text)),
+            );
+        }
+        rows
+    };
+    for entry in &line.allowances_charges {
+        let amount = layout.amount(&entry.amount, "");
+        rows.push((font, small, adjustment_label(entry, &amount)));
+    }
+    if !line.order_line_id.is_empty() {
+        rows.push((font, small, format!("PO line: {}", line.order_line_id)));
+    }
+    rows
+}
+
+/// Draws one item row with its already wrapped [`item_rows`] and returns
+/// the y position of the next row.
+fn draw_line_item(
+    layer: &PdfLayerReference,
+    font: &Font,
+    layout: &Layout,
+    currency: &str,
+    line: &InvoiceLine,
+    rows: Vec<(&Font, f64, String)>,
+    mut y: f64,
+) -> f64 {
+    let size = layout.size(0.9);
+    write_text_right_aligned(
+        layer,
+        font,
+        size,
+        layout.qty_right,
+        y,
+        &line.quantity_with_unit(),
+    );
+    write_text_right_aligned(
+        layer,
+        font,
+        size,
+        layout.unit_right,
+        y,
+        &layout.price(&line.unit_price, currency),
+    );
+    if !line.tax_percent.is_empty() {
+        let rate = format!("{}%", line.tax_percent);
+        write_text_right_aligned(layer, font, size, layout.tax_right, y, &rate);
+    }
+    write_text_right_aligned(
+        layer,
+        font,
+        size,
+        layout.content_right,
+        y,
+        &layout.amount(&line.total, currency),
+    );
+    for (row_font, row_size, text) in rows {
+        write_text(layer, row_font, row_size, layout.left_x, y, &text);
+        y -= layout.line_height;
+    }
+    y
+}
+
+/// Approximate Helvetica advance width in mm, using per-class glyph widths
+/// from the font metrics (digits share one width, so amounts line up).
+fn text_width(text: &str, size: f64) -> f64 {
+    let em: f64 = text
+        .chars()
+        .map(|ch| match ch {
+            '0'..='9' => 0.556,
+            ' ' | '.' | ',' | ':' | ';' | '\'' | '!' | '|' => 0.278,
+            'i' | 'j' | 'l' => 0.222,
+            'f' | 't' | 'r' | '-' | '(' | ')' | '/' => 0.333,
+            'm' | 'w' | 'M' | 'W' | '%' | '@' => 0.889,
+            'a'..='z' => 0.5,
+            'A'..='Z' => 0.667,
+            _ => 0.556,
+        })
+        .sum();
+    em * size * PT_TO_MM
+}
+
+/// Greedily breaks `text` into lines no wider than `max_width` mm. Always
+/// yields at least one (possibly empty) line.
+fn wrap_text(font: &Font, text: &str, size: f64, max_width: f64) -> Vec<String> {
+    // Widths are additive, so track the current line's width instead of
+    // re-measuring it for every word.
+    let space = font.width(" ", size);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut current_width = 0.0;
+    for word in text.split_whitespace() {
+        let word_width = font.width(word, size);
+        let candidate_width = if current.is_empty() {
+            word_width
+        } else {
+            current_width + space + word_width
+        };
+        if candidate_width <= max_width {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+            current_width = candidate_width;
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        // Hard-break words that are wider than the column on their own.
+        current_width = 0.0;
+        for ch in word.chars() {
+            let ch_width = font.width(ch.encode_utf8(&mut [0; 4]), size);
+            if current_width + ch_width > max_width && !current.is_empty() {
+                lines.push(std::mem::replace(&mut current, ch.to_string()));
+                current_width = ch_width;
+            } else {
+                current.push(ch);
+                current_width += ch_width;
+            }
+        }
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Characters of the WinAnsi encoding used by the built-in fonts.
+fn is_win_ansi(ch: char) -> bool {
+    matches!(ch, ' '..='~' | '\u{a0}'..='\u{ff}') || "€‚ƒ„…†‡ˆ‰Š‹ŒŽ‘’“”•–—˜™š›œžŸ".contains(ch)
+}
+
+/// Draws `text`, replacing characters the font cannot encode with `?` and
+/// remembering the first one so rendering can report it.
+fn write_text(layer: &PdfLayerReference, font: &Font, size: f64, x: f64, y: f64, text: &str) {
+    let text: String = text
+        .chars()
+        .map(|ch| {
+            if font.encodes(ch) {
+                return ch;
+            }
+            if font.missing.get().is_none() {
+                font.missing.set(Some(ch));
+            }
+            '?'
+        })
+        .collect();
+    layer.use_text(
+        text,
+        size as f32,
+        Mm(x as f32),
+        Mm(y as f32),
+        &font.reference,
+    );
+}
+
+/// Makes the text row with its baseline at `y`, from `left` to `right`,
+/// open `uri` when clicked. The link has no visible border.
+fn add_link(layer: &PdfLayerReference, uri: &str, left: f64, y: f64, right: f64, size: f64) {
+    let bottom = y - size * PT_TO_MM * 0.25;
+    let top = y + size * PT_TO_MM;
+    layer.add_link_annotation(LinkAnnotation::new(
+        Rect::new(
+            Mm(left as f32),
+            Mm(bottom as f32),
+            Mm(right as f32),
+            Mm(top as f32),
+        ),
+        Some(BorderArray::Solid([0.0, 0.0, 0.0])),
+        None,
+        Actions::uri(uri.to_string()),
+        None,
+    ));
+}
+
+/// Draws a party name or address line in its `(left, right)` column. With
+/// `rtl`, text whose first strong character is right-to-left is reordered
+/// into visual order and aligned to the right edge.
+fn write_party_text(
+    layer: &PdfLayerReference,
+    font: &Font,
+    size: f64,
+    (left, right): (f64, f64),
+    y: f64,
+    text: &str,
+    rtl: bool,
+) {
+    if rtl {
+        let (visual, right_to_left) = visual_order(text);
+        if right_to_left {
+            write_text_right_aligned(layer, font, size, right, y, &visual);
+        } else {
+            write_text(layer, font, size, left, y, &visual);
+        }
+    } else {
+        write_text(layer, font, size, left, y, text);
+    }
+}
+
+/// Reorders `text` from logical (typed) to visual (drawn) order with the
+/// Unicode bidirectional algorithm, and tells whether its base direction,
+/// taken from the first strong character, is right-to-left.
+fn visual_order(text: &str) -> (String, bool) {
+    let bidi = BidiInfo::new(text, None);
+    let right_to_left = bidi
+        .paragraphs
+        .first()
+        .is_some_and(|paragraph| paragraph.level.is_rtl());
+    let visual = bidi
+        .paragraphs
+        .iter()
+        .map(|paragraph| bidi.reorder_line(paragraph, paragraph.range.clone()))
+        .collect();
+    (visual, right_to_left)
+}
+
+/// Draws a totals row: the label at the totals column, the amount flush right.
+fn write_total_row(
+    layer: &PdfLayerReference,
+    font: &Font,
+    layout: &Layout,
+    size: f64,
+    y: f64,
+    label: &str,
+    amount: &str,
+) {
+    write_text(layer, font, size, layout.totals_label_x, y, label);
+    write_text_right_aligned(layer, font, size, layout.content_right, y, amount);
+}
+
+/// Draws `text` so that it ends at `right_x`, using the estimated text width.
+fn write_text_right_aligned(
+    layer: &PdfLayerReference,
+    font: &Font,
+    size: f64,
+    right_x: f64,
+    y: f64,
+    text: &str,
+) {
+    write_text(layer, font, size, right_x - font.width(text, size), y, text);
+}
+
+/// Fills the band behind an item row whose first baseline is at `y`, from
+/// above the cap height down past the descenders of its last wrapped row.
+fn draw_stripe(layer: &PdfLayerReference, layout: &Layout, y: f64, row_height: f64) {
+    let top = y + layout.line_height * 0.7;
+    layer.set_fill_color(rgb(layout.palette.stripe));
+    layer.add_rect(Rect::new(
+        Mm(layout.left_x as f32),
+        Mm((top - row_height) as f32),
+        Mm(layout.content_right as f32),
+        Mm(top as f32),
+    ));
+    layer.set_fill_color(rgb(layout.palette.text));
+}
+
+/// Draws a rule across the content width at `y`.
+fn draw_divider(layer: &PdfLayerReference, layout: &Layout, y: f64) {
+    let (x1, x2) = (layout.left_x, layout.content_right);
+    layer.set_outline_thickness(0.3);
+    layer.set_outline_color(rgb(layout.palette.divider));
+    let line = Line {
+        points: vec![
+            (Point::new(Mm(x1 as f32), Mm(y as f32)), false),
+            (Point::new(Mm(x2 as f32), Mm(y as f32)), false),
+        ],
+        is_closed: false,
+    };
+    layer.add_line(line);
+    layer.set_outline_color(rgb((0.0, 0.0, 0.0)));
+    layer.set_outline_thickness(1.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::SAMPLE_XML;
+    use crate::{AllowanceCharge, parse_ubl_invoice};
+    use printpdf::lopdf::Object;
+
+    /// Built-in font text is written as a hex string operand, one WinAnsi
+    /// byte per character, which covers the ASCII and Latin-1 test strings.
+    fn hex(text: &str) -> String {
+        text.chars()
+            .map(|ch| format!("{:02X}", u32::from(ch)))
+            .collect()
+    }
+
+    /// Whether any page of `pdf` draws `text` with a built-in font.
+    fn page_text_contains(pdf: &[u8], text: &str) -> bool {
+        let hex = hex(text);
+        let pdf = lopdf::Document::load_mem(pdf).expect("load pdf");
+        pdf.get_pages().values().any(|page_id| {
+            let content = pdf.get_page_content(*page_id).expect("page content");
+            content
+                .windows(hex.len())
+                .any(|window| window == hex.as_bytes())
+        })
+    }
+
+    #[test]
+    fn long_invoices_flow_onto_extra_pages() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let line = data.lines[0].clone();
+        data.lines = vec![line; 100];
+
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        assert!(pdf.get_pages().len() > 1);
+    }
+
+    #[test]
+    fn wraps_long_descriptions_onto_multiple_rows() {
+        let (doc, page, layer) = PdfDocument::new("Test", Mm(210.0), Mm(297.0), "Layer 1");
+        let font = Font::load(&doc, None, BuiltinFont::Helvetica).expect("load font");
+        let layer = doc.get_page(page).get_layer(layer);
+        let layout = Layout::new(&InvoiceStyle::default());
+        let line = InvoiceLine {
+            name: String::new(),
+            description: "Consulting services rendered ".repeat(8)[..200].to_string(),
+            quantity: "1".to_string(),
+            unit_code: "C62".to_string(),
+            unit_price: "10.00".to_string(),
+            total: "10.00".to_string(),
+            tax_percent: "19".to_string(),
+            tax_category: "S".to_string(),
+            order_line_id: String::new(),
+            seller_item_id: String::new(),
+            buyer_item_id: String::new(),
+            allowances_charges: Vec::new(),
+        };
+
+        let rows = item_rows(&font, &font, &layout, &line);
+        let end = draw_line_item(&layer, &font, &layout, "EUR", &line, rows, 200.0);
+        assert!(200.0 - end >= 3.0 * layout.line_height);
+        for text in wrap_text(&font, &line.description, 9.0, layout.description_width) {
+            assert!(text_width(&text, 9.0) <= layout.description_width);
+        }
+    }
+
+    #[test]
+    fn notes_line_discounts_under_the_item() {
+        let (doc, _, _) = PdfDocument::new("Test", Mm(210.0), Mm(297.0), "Layer 1");
+        let font = Font::load(&doc, None, BuiltinFont::Helvetica).expect("load font");
+        let layout = Layout::new(&InvoiceStyle::default());
+        let line = InvoiceLine {
+            description: "Widget".to_string(),
+            allowances_charges: vec![
+                AllowanceCharge {
+                    is_charge: false,
+                    amount: "2".to_string(),
+                    reason: String::new(),
+                },
+                AllowanceCharge {
+                    is_charge: true,
+                    amount: "0.50".to_string(),
+                    reason: "Packaging".to_string(),
+                },
+            ],
+            ..InvoiceLine::default()
+        };
+
+        let rows: Vec<String> = item_rows(&font, &font, &layout, &line)
+            .into_iter()
+            .map(|(_, _, text)| text)
+            .collect();
+        assert_eq!(rows, ["Widget", "-2.00 discount", "+0.50 Packaging"]);
+    }
+
+    #[test]
+    fn estimates_text_width_from_font_size() {
+        assert_eq!(text_width("", 10.0), 0.0);
+        // All digits share one advance width, so amounts align on the right.
+        assert!((text_width("1234.00", 10.0) - text_width("9999.99", 10.0)).abs() < 1e-9);
+        assert!(text_width("9.00", 10.0) < text_width("1234.00", 10.0));
+        assert!((text_width("00", 20.0) - 2.0 * text_width("00", 10.0)).abs() < 1e-9);
+        let expected = (4.0 * 0.556 + 0.278) * 10.0 * PT_TO_MM;
+        assert!((text_width("12.34", 10.0) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn renders_pdf_to_memory() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn reorders_right_to_left_text_for_display() {
+        // Hebrew base direction: the Latin run keeps its order but moves left.
+        assert_eq!(
+            visual_order("שלום ABC 12"),
+            ("ABC 12 םולש".to_string(), true)
+        );
+        // Latin base direction: only the Hebrew run is reversed.
+        assert_eq!(
+            visual_order("Order 12 שלום"),
+            ("Order 12 םולש".to_string(), false)
+        );
+        assert_eq!(visual_order("Acme GmbH"), ("Acme GmbH".to_string(), false));
+    }
+
+    #[test]
+    fn renders_pdf_into_a_writer() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let mut bytes = Vec::new();
+        write_invoice_pdf(&data, &mut bytes).expect("write pdf");
+        assert!(bytes.starts_with(b"%PDF"));
+        lopdf::Document::load_mem(&bytes).expect("load pdf");
+    }
+
+    #[test]
+    fn default_style_matches_classic_layout() {
+        let layout = Layout::new(&InvoiceStyle::default());
+        assert_eq!(layout.left_x, 18.0);
+        assert_eq!(layout.right_x, 110.0);
+        assert_eq!(layout.content_right, 192.0);
+        assert_eq!(layout.description_width, 86.0);
+        assert!((layout.line_height - 6.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn renders_with_custom_style() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let style = InvoiceStyle {
+            accent_color: (0.6, 0.1, 0.1),
+            left_margin: 25.0,
+            right_margin: 25.0,
+            base_font_size: 11.0,
+            ..InvoiceStyle::default()
+        };
+        let bytes = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn builds_epc_payload_and_renders_qr() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(epc_qr_payload(&data).is_none());
+
+        data.payment_iban = "FR76 3000 6000 0112 3456 7890 189".to_string();
+        data.payment_bic = "AGRIFRPP".to_string();
+        let payload = epc_qr_payload(&data).expect("payload");
+        assert_eq!(
+            payload,
+            "BCD\n002\n1\nSCT\nAGRIFRPP\nSupplier Inc\nFR7630006000011234567890189\nEUR12.00\n\n\nINV-1"
+        );
+
+        let style = InvoiceStyle {
+            payment_qr: true,
+            ..InvoiceStyle::default()
+        };
+        let bytes = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+        assert!(bytes.starts_with(b"%PDF"));
+    }
+
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let image = ImageBuffer::from_pixel(width, height, image_crate::Rgba([200u8, 30, 30, 128]));
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_to(
+                &mut Cursor::new(&mut bytes),
+                image_crate::ImageOutputFormat::Png,
+            )
+            .expect("encode png");
+        bytes
+    }
+
+    #[test]
+    fn renders_logo_and_skips_unreadable_ones() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let logo = png_bytes(120, 40);
+        validate_logo(&logo).expect("valid logo");
+        let style = InvoiceStyle {
+            logo: Some(logo),
+            payment_qr: true,
+            ..InvoiceStyle::default()
+        };
+        let with_logo = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+        let without_logo = create_invoice_pdf_bytes(&data).expect("create pdf");
+        assert!(with_logo.len() > without_logo.len());
+
+        let broken = b"not an image".to_vec();
+        assert!(validate_logo(&broken).is_err());
+        let style = InvoiceStyle {
+            logo: Some(broken),
+            ..InvoiceStyle::default()
+        };
+        create_invoice_pdf_bytes_styled(&data, &style).expect("skip broken logo");
+    }
+
+    #[test]
+    fn rejects_oversized_logos() {
+        assert!(validate_logo(&png_bytes(LOGO_MAX_PIXELS + 1, 1)).is_err());
+    }
+
+    #[test]
+    fn embeds_unicode_font_for_cyrillic_text() {
+        const ROBOTO: &[u8] = include_bytes!("../tests/fixtures/fonts/RobotoMedium.ttf");
+        validate_font(ROBOTO).expect("valid font");
+        assert!(validate_font(b"not a font").is_err());
+
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.supplier_name = "ООО Ромашка".to_string();
+        let style = InvoiceStyle {
+            font: Some(ROBOTO.to_vec()),
+            ..InvoiceStyle::default()
+        };
+        let bytes = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        let embedded = pdf
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .any(|dict| dict.has(b"FontFile2"));
+        assert!(embedded);
+
+        let (doc, _, _) = PdfDocument::new("Test", Mm(210.0), Mm(297.0), "Layer 1");
+        let font = Font::load(&doc, Some(ROBOTO), BuiltinFont::Helvetica).expect("load font");
+        let face = font.face.as_ref().expect("embedded face");
+        assert!("Ромашка".chars().all(|ch| face.glyph_index(ch).is_some()));
+        assert!(font.width("Ромашка", 10.0) > 0.0);
+    }
+
+    #[test]
+    fn numbers_pages_in_the_footer() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let line = data.lines[0].clone();
+        data.lines = vec![line; 60];
+        let style = InvoiceStyle {
+            generated_on: Some("2024-05-01".to_string()),
+            ..InvoiceStyle::default()
+        };
+        let bytes = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        assert_eq!(pdf.get_pages().len(), 2);
+        for number in 1..=2 {
+            let footer = format!("Page {number} of 2 - generated 2024-05-01");
+            assert!(
+                page_text_contains(&bytes, &footer),
+                "missing footer on page {number}"
+            );
+        }
+
+        let today = today_utc().expect("clock");
+        assert_eq!(today.len(), 10);
+        assert!(today.as_str() >= "2024-01-01");
+    }
+
+    #[test]
+    fn renders_notes_below_the_totals() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.notes = vec![
+            "Payment within 30 days.".to_string(),
+            "Thank you for your business! ".repeat(40),
+        ];
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        for text in ["Notes", "Payment within 30 days."] {
+            assert!(page_text_contains(&bytes, text), "missing {text:?}");
+        }
+
+        // Notes that do not fit push onto a new page instead of overflowing.
+        data.notes = vec!["Thank you for your business! ".repeat(150)];
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        assert_eq!(pdf.get_pages().len(), 2);
+    }
+
+    #[test]
+    fn links_external_references() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.external_references = vec![
+            "https://example.com/timesheet.pdf".to_string(),
+            "javascript:alert(1)".to_string(),
+        ];
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        let page_id = *pdf.get_pages().values().next().expect("first page");
+        assert!(page_text_contains(
+            &bytes,
+            "https://example.com/timesheet.pdf"
+        ));
+        // Other schemes are printed but never made clickable.
+        assert!(page_text_contains(&bytes, "javascript:alert(1)"));
+
+        let page = pdf.get_dictionary(page_id).expect("page");
+        let annots = page
+            .get(b"Annots")
+            .and_then(Object::as_array)
+            .expect("annotations");
+        assert_eq!(annots.len(), 1);
+        let link = pdf
+            .get_dictionary(annots[0].as_reference().expect("annotation reference"))
+            .expect("link annotation");
+        let action = link.get(b"A").and_then(Object::as_dict).expect("action");
+        assert_eq!(
+            action.get(b"URI").and_then(Object::as_str).ok(),
+            Some(&b"https://example.com/timesheet.pdf"[..])
+        );
+    }
+
+    #[test]
+    fn renders_unit_prices_with_their_own_precision() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.lines[0].quantity = "4".to_string();
+        data.lines[0].unit_price = "2.5125".to_string();
+        data.lines[0].total = "10.05".to_string();
+        let contains = |style: &InvoiceStyle, text: &str| {
+            let bytes = create_invoice_pdf_bytes_styled(&data, style).expect("create pdf");
+            page_text_contains(&bytes, text)
+        };
+
+        let style = InvoiceStyle::default();
+        assert!(contains(&style, "EUR 2.5125"));
+        assert!(contains(&style, "EUR 10.05"));
+        let style = InvoiceStyle {
+            price_decimals: 2,
+            ..InvoiceStyle::default()
+        };
+        assert!(contains(&style, "EUR 2.51"));
+        assert!(!contains(&style, "EUR 2.5125"));
+    }
+
+    #[test]
+    fn shows_rounding_and_amount_due_below_the_total() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.total = "12.03".to_string();
+        data.rounding_amount = "-0.03".to_string();
+        data.payable_amount = "12.00".to_string();
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        for text in [
+            "Total:",
+            "EUR 12.03",
+            "Rounding:",
+            "EUR -0.03",
+            "Amount due:",
+        ] {
+            assert!(page_text_contains(&bytes, text), "missing {text:?}");
+        }
+
+        // The payment QR code asks for the amount due, not the total.
+        data.payment_iban = "FR7630006000011234567890189".to_string();
+        let payload = epc_qr_payload(&data).expect("payload");
+        assert!(payload.contains("\nEUR12.00\n"));
+    }
+
+    #[test]
+    fn shows_prepaid_deposits_and_the_remaining_amount_due() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.prepaid_amount = "5.00".to_string();
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        for text in ["Prepaid:", "EUR -5.00", "Amount due:", "EUR 7.00"] {
+            assert!(page_text_contains(&bytes, text), "missing {text:?}");
+        }
+    }
+
+    #[test]
+    fn zebra_shades_every_other_item_row() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let mut wrapped = data.lines[0].clone();
+        wrapped.description = "A long description that wraps. ".repeat(8);
+        data.lines = vec![data.lines[0].clone(), wrapped, data.lines[0].clone()];
+        data.lines.extend(data.lines.clone());
+        let stripes = |zebra: bool| {
+            let style = InvoiceStyle {
+                zebra,
+                ..InvoiceStyle::default()
+            };
+            let bytes = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+            let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+            let page_id = *pdf.get_pages().values().next().expect("first page");
+            let content = pdf.get_page_content(page_id).expect("page content");
+            let gray = b"0.94 0.94 0.94 rg";
+            content
+                .windows(gray.len())
+                .filter(|window| window == gray)
+                .count()
+        };
+        assert_eq!(stripes(false), 0);
+        assert_eq!(stripes(true), 3);
+    }
+
+    #[test]
+    fn every_theme_renders() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let classic = InvoiceStyle::from_theme(Theme::Classic);
+        let default = InvoiceStyle::default();
+        assert_eq!(classic.accent_color, default.accent_color);
+        assert_eq!(classic.text_color, default.text_color);
+        assert_eq!(classic.background_color, None);
+
+        for theme in [Theme::Classic, Theme::Minimal, Theme::Dark] {
+            let style = InvoiceStyle::from_theme(theme);
+            let bytes = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+            assert!(
+                page_text_contains(&bytes, "INV-1"),
+                "{theme:?} lost the invoice number"
+            );
+            let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+            let page_id = *pdf.get_pages().values().next().expect("first page");
+            let content = pdf.get_page_content(page_id).expect("page content");
+            let content = String::from_utf8_lossy(&content);
+            // Only the dark theme paints the page before drawing on it.
+            let background = content.contains("0.12 0.13 0.15 rg");
+            assert_eq!(background, theme == Theme::Dark, "{theme:?}");
+        }
+    }
+
+    #[test]
+    fn prints_the_legal_name_under_a_different_trading_name() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.supplier_legal_name = "Supplier Holding SA".to_string();
+        data.customer_legal_name = data.customer_name.clone();
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        let page_id = *pdf.get_pages().values().next().expect("first page");
+        let content = pdf.get_page_content(page_id).expect("page content");
+        let count = |text: &str| {
+            let hex = hex(text);
+            content
+                .windows(hex.len())
+                .filter(|window| *window == hex.as_bytes())
+                .count()
+        };
+        assert_eq!(count("Supplier Holding SA"), 1);
+        assert_eq!(count("Customer LLC"), 1);
+    }
+
+    #[test]
+    fn titles_follow_the_type_code() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let contains = |data: &InvoiceData, text: &str| {
+            let bytes = create_invoice_pdf_bytes(data).expect("create pdf");
+            page_text_contains(&bytes, text)
+        };
+        assert!(!contains(&data, "CORRECTED"));
+
+        data.type_code = "384".to_string();
+        assert!(contains(&data, "INVOICE"));
+        assert!(contains(&data, "CORRECTED"));
+
+        data.type_code = "383".to_string();
+        assert!(contains(&data, "DEBIT NOTE"));
+    }
+
+    #[test]
+    fn strict_fonts_reject_glyphs_outside_win_ansi() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.supplier_name = "Café Ωmega".to_string();
+        let strict = InvoiceStyle {
+            strict_fonts: true,
+            ..InvoiceStyle::default()
+        };
+        assert!(matches!(
+            create_invoice_pdf_bytes_styled(&data, &strict),
+            Err(InvoiceError::UnsupportedGlyph('Ω'))
+        ));
+
+        // Lenient rendering keeps the WinAnsi "é" and prints a placeholder.
+        let bytes = create_invoice_pdf_bytes(&data).expect("create pdf");
+        assert!(page_text_contains(&bytes, "Café ?mega"));
+
+        data.supplier_name = "Café €".to_string();
+        assert!(create_invoice_pdf_bytes_styled(&data, &strict).is_ok());
+    }
+
+    #[test]
+    fn renders_us_letter_pages() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        let style = InvoiceStyle {
+            page_size: PageSize::Letter,
+            ..InvoiceStyle::default()
+        };
+        let layout = Layout::new(&style);
+        assert!((layout.content_right - 197.9).abs() < 1e-9);
+
+        let bytes = create_invoice_pdf_bytes_styled(&data, &style).expect("create pdf");
+        let pdf = lopdf::Document::load_mem(&bytes).expect("load pdf");
+        let page_id = *pdf.get_pages().values().next().expect("first page");
+        let page = pdf.get_dictionary(page_id).expect("page dictionary");
+        let media_box = page.get(b"MediaBox").and_then(lopdf::Object::as_array);
+        let points: Vec<f32> = media_box
+            .expect("media box")
+            .iter()
+            .map(|value| value.as_float().expect("number"))
+            .collect();
+        // 8.5 × 11 inches at 72 points per inch.
+        assert!((points[2] - 612.0).abs() < 0.5);
+        assert!((points[3] - 792.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn columns_scale_with_the_content_width() {
+        let style = InvoiceStyle {
+            page_size: PageSize::Custom {
+                width_mm: 105.0,
+                height_mm: 148.0,
+            },
+            left_margin: 10.0,
+            right_margin: 10.0,
+            ..InvoiceStyle::default()
+        };
+        let layout = Layout::new(&style);
+        assert_eq!(layout.content_right, 95.0);
+        let columns = [
+            layout.left_x + layout.description_width,
+            layout.qty_right,
+            layout.unit_right,
+            layout.tax_right,
+            layout.content_right,
+        ];
+        assert!(columns.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(layout.description_width > 0.0);
+        assert!(layout.totals_label_x > layout.left_x);
+        assert!(layout.right_x < layout.content_right);
+
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert!(create_invoice_pdf_bytes_styled(&data, &style).is_ok());
+    }
+}
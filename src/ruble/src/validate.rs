@@ -0,0 +1,395 @@
+//! Pre-flight checks for the EN 16931 business terms this crate parses.
+
+use crate::InvoiceData;
+use crate::format::parse_amount;
+
+/// Checks that the mandatory EN 16931 business terms ruble extracts are
+/// present, returning every problem found rather than stopping at the first.
+pub fn validate_invoice(data: &InvoiceData) -> Result<(), Vec<String>> {
+    let required = [
+        ("BT-1", "invoice number", &data.invoice_number),
+        ("BT-2", "issue date", &data.issue_date),
+        ("BT-5", "currency code", &data.currency),
+        ("BT-27", "seller name", &data.supplier_name),
+        ("BT-44", "buyer name", &data.customer_name),
+    ];
+    let mut problems: Vec<String> = required
+        .iter()
+        .filter(|(_, _, value)| value.trim().is_empty())
+        .map(|(term, name, _)| format!("missing {name} ({term})"))
+        .collect();
+
+    if data.lines.is_empty() {
+        problems.push("missing invoice lines (BG-25)".to_string());
+    }
+    for (index, line) in data.lines.iter().enumerate() {
+        if line.total.trim().is_empty() {
+            problems.push(format!(
+                "line {}: missing line net amount (BT-131)",
+                index + 1
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// IBAN length per country, from the SWIFT IBAN registry.
+const IBAN_LENGTHS: &[(&str, usize)] = &[
+    ("AD", 24),
+    ("AE", 23),
+    ("AL", 28),
+    ("AT", 20),
+    ("AZ", 28),
+    ("BA", 20),
+    ("BE", 16),
+    ("BG", 22),
+    ("BH", 22),
+    ("BR", 29),
+    ("BY", 28),
+    ("CH", 21),
+    ("CR", 22),
+    ("CY", 28),
+    ("CZ", 24),
+    ("DE", 22),
+    ("DK", 18),
+    ("DO", 28),
+    ("EE", 20),
+    ("EG", 29),
+    ("ES", 24),
+    ("FI", 18),
+    ("FO", 18),
+    ("FR", 27),
+    ("GB", 22),
+    ("GE", 22),
+    ("GI", 23),
+    ("GL", 18),
+    ("GR", 27),
+    ("GT", 28),
+    ("HR", 21),
+    ("HU", 28),
+    ("IE", 22),
+    ("IL", 23),
+    ("IQ", 23),
+    ("IS", 26),
+    ("IT", 27),
+    ("JO", 30),
+    ("KW", 30),
+    ("KZ", 20),
+    ("LB", 28),
+    ("LC", 32),
+    ("LI", 21),
+    ("LT", 20),
+    ("LU", 20),
+    ("LV", 21),
+    ("MC", 27),
+    ("MD", 24),
+    ("ME", 22),
+    ("MK", 19),
+    ("MR", 27),
+    ("MT", 31),
+    ("MU", 30),
+    ("NL", 18),
+    ("NO", 15),
+    ("PK", 24),
+    ("PL", 28),
+    ("PS", 29),
+    ("PT", 25),
+    ("QA", 29),
+    ("RO", 24),
+    ("RS", 22),
+    ("SA", 24),
+    ("SC", 31),
+    ("SE", 24),
+    ("SI", 19),
+    ("SK", 24),
+    ("SM", 27),
+    ("ST", 25),
+    ("SV", 28),
+    ("TL", 23),
+    ("TN", 24),
+    ("TR", 26),
+    ("UA", 29),
+    ("VA", 22),
+    ("VG", 24),
+    ("XK", 20),
+];
+
+/// Checks an IBAN's length for its country and its ISO 7064 mod-97 check
+/// digits. Spaces and lowercase letters are accepted, as printed on invoices.
+/// Countries missing from the registry table only get the 15–34 character
+/// bounds.
+pub fn iban_is_valid(iban: &str) -> bool {
+    let iban: String = iban
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .map(|ch| ch.to_ascii_uppercase())
+        .collect();
+    let bytes = iban.as_bytes();
+    if bytes.len() < 15
+        || bytes.len() > 34
+        || !bytes[..2].iter().all(u8::is_ascii_uppercase)
+        || !bytes[2..4].iter().all(u8::is_ascii_digit)
+        || !bytes.iter().all(u8::is_ascii_alphanumeric)
+    {
+        return false;
+    }
+    let country = &iban[..2];
+    if let Some((_, length)) = IBAN_LENGTHS.iter().find(|(code, _)| *code == country)
+        && bytes.len() != *length
+    {
+        return false;
+    }
+    // Move the country and check digits to the end and read letters as
+    // 10..=35; the resulting number must leave a remainder of 1.
+    let remainder = bytes[4..]
+        .iter()
+        .chain(&bytes[..4])
+        .fold(0u32, |remainder, &byte| {
+            if byte.is_ascii_digit() {
+                (remainder * 10 + u32::from(byte - b'0')) % 97
+            } else {
+                (remainder * 100 + u32::from(byte - b'A' + 10)) % 97
+            }
+        });
+    remainder == 1
+}
+
+/// Logs a warning for a parsed IBAN that fails [`iban_is_valid`].
+pub(crate) fn warn_on_invalid_iban(iban: &str) {
+    if !iban.is_empty() && !iban_is_valid(iban) {
+        tracing::warn!(iban, "IBAN fails the checksum or length check");
+    }
+}
+
+/// The IBAN as printed on the invoice, marked when it fails the check so a
+/// corrupted account number is not copied into a transfer unnoticed.
+pub(crate) fn iban_display(iban: &str) -> String {
+    if iban_is_valid(iban) {
+        iban.to_string()
+    } else {
+        format!("{iban} (invalid)")
+    }
+}
+
+/// Differences below half a cent are rounding, not a malformed document.
+const TOLERANCE: f64 = 0.005;
+
+/// Cross-checks the parsed amounts: line net amounts less allowances plus
+/// charges must equal the subtotal (BT-109), and subtotal plus VAT the total
+/// (BT-112), and the total less prepayments plus rounding the amount due
+/// (BT-115). Missing or non-numeric amounts are skipped rather than reported.
+pub fn check_totals(data: &InvoiceData) -> Vec<String> {
+    let mut problems = Vec::new();
+    let subtotal = parse_amount(&data.subtotal);
+
+    let line_sum: Option<f64> = data
+        .lines
+        .iter()
+        .map(|line| parse_amount(&line.total))
+        .sum();
+    let adjustments: Option<f64> = data
+        .allowances_charges
+        .iter()
+        .map(|entry| {
+            let amount = parse_amount(&entry.amount)?;
+            Some(if entry.is_charge { amount } else { -amount })
+        })
+        .sum();
+    if let (Some(line_sum), Some(adjustments), Some(subtotal)) = (line_sum, adjustments, subtotal)
+        && !data.lines.is_empty()
+    {
+        let expected = line_sum + adjustments;
+        if (expected - subtotal).abs() > TOLERANCE {
+            let basis = if data.allowances_charges.is_empty() {
+                "line totals"
+            } else {
+                "line totals with allowances and charges"
+            };
+            problems.push(format!(
+                "{basis} sum to {expected:.2} but the subtotal is {subtotal:.2}"
+            ));
+        }
+    }
+
+    if let (Some(subtotal), Some(tax), Some(total)) = (
+        subtotal,
+        parse_amount(&data.tax_total),
+        parse_amount(&data.total),
+    ) && (subtotal + tax - total).abs() > TOLERANCE
+    {
+        problems.push(format!(
+            "subtotal {subtotal:.2} plus VAT {tax:.2} is {:.2} but the total is {total:.2}",
+            subtotal + tax
+        ));
+    }
+
+    if let (Some(total), Some(payable)) = (
+        parse_amount(&data.total),
+        parse_amount(&data.payable_amount),
+    ) {
+        let prepaid = parse_amount(&data.prepaid_amount).unwrap_or(0.0);
+        let rounding = parse_amount(&data.rounding_amount).unwrap_or(0.0);
+        let expected = total - prepaid + rounding;
+        if (expected - payable).abs() > TOLERANCE {
+            problems.push(format!(
+                "total {total:.2} less prepaid {prepaid:.2} plus rounding {rounding:.2} \
+                 is {expected:.2} but the amount due is {payable:.2}"
+            ));
+        }
+    }
+    problems
+}
+
+/// Reports document totals whose `currencyID` differs from the document
+/// currency (BT-5), a sign of a broken document; the amounts themselves are
+/// still read in the document currency.
+pub fn check_currencies(data: &InvoiceData) -> Vec<String> {
+    data.total_currencies
+        .iter()
+        .filter(|(_, currency)| !data.currency.is_empty() && *currency != data.currency)
+        .map(|(element, currency)| {
+            format!(
+                "{element} is in {currency} but the document currency is {}",
+                data.currency
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::SAMPLE_XML;
+    use crate::{AllowanceCharge, parse_ubl_invoice};
+
+    #[test]
+    fn reports_all_missing_business_terms() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(validate_invoice(&data), Ok(()));
+
+        let mut broken = data.clone();
+        broken.invoice_number.clear();
+        broken.customer_name = "  ".to_string();
+        broken.lines[0].total.clear();
+        let problems = validate_invoice(&broken).expect_err("invalid invoice");
+        assert_eq!(
+            problems,
+            vec![
+                "missing invoice number (BT-1)",
+                "missing buyer name (BT-44)",
+                "line 1: missing line net amount (BT-131)",
+            ]
+        );
+
+        broken.lines.clear();
+        let problems = validate_invoice(&broken).expect_err("invalid invoice");
+        assert!(problems.contains(&"missing invoice lines (BG-25)".to_string()));
+    }
+
+    #[test]
+    fn balanced_totals_pass_the_sum_check() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(check_totals(&data), Vec::<String>::new());
+
+        data.lines.push(data.lines[0].clone());
+        data.allowances_charges.push(AllowanceCharge {
+            is_charge: false,
+            amount: "10.00".to_string(),
+            reason: "Discount".to_string(),
+        });
+        assert_eq!(check_totals(&data), Vec::<String>::new());
+    }
+
+    #[test]
+    fn comma_decimal_totals_are_checked() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.tax_total = "2,00".to_string();
+        data.total = "12,00".to_string();
+        assert_eq!(check_totals(&data), Vec::<String>::new());
+        data.total = "13,00".to_string();
+        assert_eq!(check_totals(&data).len(), 1);
+    }
+
+    #[test]
+    fn amount_due_is_checked_against_prepaid_and_rounding() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.prepaid_amount = "5.00".to_string();
+        data.payable_amount = "7.00".to_string();
+        assert_eq!(check_totals(&data), Vec::<String>::new());
+        data.payable_amount = "12.00".to_string();
+        assert_eq!(
+            check_totals(&data),
+            vec![
+                "total 12.00 less prepaid 5.00 plus rounding 0.00 is 7.00 but the amount due is 12.00"
+            ]
+        );
+    }
+
+    #[test]
+    fn total_currencies_must_match_the_document_currency() {
+        let data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        assert_eq!(check_currencies(&data), Vec::<String>::new());
+
+        let xml = SAMPLE_XML
+            .replace(
+                "<cbc:TaxExclusiveAmount>",
+                r#"<cbc:TaxExclusiveAmount currencyID="EUR">"#,
+            )
+            .replace(
+                "<cbc:TaxInclusiveAmount>",
+                r#"<cbc:TaxInclusiveAmount currencyID="USD">"#,
+            );
+        let data = parse_ubl_invoice(&xml).expect("parse invoice");
+        assert_eq!(data.currency, "EUR");
+        assert_eq!(
+            check_currencies(&data),
+            vec!["TaxInclusiveAmount is in USD but the document currency is EUR"]
+        );
+    }
+
+    #[test]
+    fn accepts_ibans_with_valid_check_digits() {
+        assert!(iban_is_valid("FR7630006000011234567890189"));
+        assert!(iban_is_valid("DE02 1203 0000 0000 2020 51"));
+        assert!(iban_is_valid("gb29 nwbk 6016 1331 9268 19"));
+    }
+
+    #[test]
+    fn rejects_corrupted_or_mis_sized_ibans() {
+        // One digit changed.
+        assert!(!iban_is_valid("FR7630006000011234567890188"));
+        // Transposed digits.
+        assert!(!iban_is_valid("DE02120300000000202015"));
+        // A digit short for Germany.
+        assert!(!iban_is_valid("DE0212030000000020205"));
+        assert!(!iban_is_valid("not an iban"));
+        assert!(!iban_is_valid(""));
+        assert_eq!(
+            iban_display("FR7630006000011234567890188"),
+            "FR7630006000011234567890188 (invalid)"
+        );
+        assert_eq!(
+            iban_display("FR7630006000011234567890189"),
+            "FR7630006000011234567890189"
+        );
+    }
+
+    #[test]
+    fn unbalanced_totals_are_reported() {
+        let mut data = parse_ubl_invoice(SAMPLE_XML).expect("parse invoice");
+        data.lines[0].total = "9.50".to_string();
+        data.total = "13.00".to_string();
+        assert_eq!(
+            check_totals(&data),
+            vec![
+                "line totals sum to 9.50 but the subtotal is 10.00",
+                "subtotal 10.00 plus VAT 2.00 is 12.00 but the total is 13.00",
+            ]
+        );
+    }
+}
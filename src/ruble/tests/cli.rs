@@ -0,0 +1,936 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+const INVOICE: &str = include_str!("fixtures/invoice.xml");
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ruble-cli-{}-{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("create scratch dir");
+    dir
+}
+
+fn ruble(args: &[&str], input: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ruble"))
+        .args(args)
+        .arg(input)
+        .output()
+        .expect("run ruble")
+}
+
+#[test]
+fn json_prints_parsed_invoice() {
+    let dir = scratch_dir("json");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+
+    let output = ruble(&["--json"], &dir);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains(r#""invoice_number": "INV-1""#));
+    assert!(stdout.contains(r#""description": "Widget""#));
+    assert!(!dir.join("invoice_INV-1_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn parallel_run_reports_failing_file_and_exits_nonzero() {
+    let dir = scratch_dir("jobs");
+    for n in 1..=4 {
+        let xml = INVOICE.replace("INV-1", &format!("INV-{n}"));
+        fs::write(dir.join(format!("invoice-{n}.xml")), xml).expect("write invoice");
+    }
+    fs::write(dir.join("broken.xml"), "<Invoice>").expect("write broken invoice");
+
+    let output = ruble(&["--jobs", "2"], &dir);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stdout.contains("Processed 4 file(s) with 1 failure(s)."));
+    assert!(stderr.contains("broken.xml"));
+    for n in 1..=4 {
+        assert!(dir.join(format!("invoice_INV-{n}_generated.pdf")).exists());
+    }
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn stdin_invoice_is_written_to_stdout_as_pdf() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ruble"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run ruble");
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(INVOICE.as_bytes())
+        .expect("write stdin");
+    let output = child.wait_with_output().expect("wait for ruble");
+
+    assert!(output.status.success());
+    assert!(output.stdout.starts_with(b"%PDF-"));
+
+    let dir = scratch_dir("stdin-output");
+    let output = Command::new(env!("CARGO_BIN_EXE_ruble"))
+        .args(["-", "--output"])
+        .arg(&dir)
+        .stdin(Stdio::null())
+        .output()
+        .expect("run ruble");
+    assert!(!output.status.success());
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn dry_run_reports_failures_without_writing() {
+    let dir = scratch_dir("dry-run");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+    fs::write(dir.join("broken.xml"), "<Invoice>").expect("write broken invoice");
+    let out_dir = dir.join("out");
+
+    let output = ruble(
+        &[
+            "--dry-run",
+            "--output",
+            out_dir.to_str().expect("utf-8 path"),
+        ],
+        &dir,
+    );
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stderr.contains("would process"));
+    assert!(stdout.contains("Processed 1 file(s) with 1 failure(s)."));
+    assert!(!out_dir.exists());
+    assert!(!dir.join("invoice_INV-1_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn quiet_prints_only_errors() {
+    let dir = scratch_dir("quiet");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+
+    let output = ruble(&["--quiet"], &dir);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+    assert!(dir.join("invoice_INV-1_generated.pdf").exists());
+
+    fs::write(dir.join("broken.xml"), "<Invoice>").expect("write broken invoice");
+    let output = ruble(&["-q"], &dir);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stderr.contains("broken.xml"));
+    assert!(!stderr.contains("invoice.xml"));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn facturx_embeds_the_source_xml_in_the_pdf() {
+    let dir = scratch_dir("facturx");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+
+    let output = ruble(&["--facturx"], &dir);
+    assert!(output.status.success());
+    let pdf = fs::read(dir.join("invoice_INV-1_generated.pdf")).expect("read pdf");
+    let contains = |needle: &[u8]| pdf.windows(needle.len()).any(|window| window == needle);
+    assert!(contains(b"/AFRelationship/Data"));
+    assert!(contains(INVOICE.as_bytes()));
+
+    let output = ruble(&["--facturx", "--format", "html"], &dir);
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn blank_files_fail_as_empty_documents() {
+    let dir = scratch_dir("blank");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+    fs::write(dir.join("empty.xml"), "").expect("write empty file");
+    fs::write(dir.join("blank.xml"), " \n\n").expect("write blank file");
+
+    let output = ruble(&[], &dir);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stdout.contains("Processed 1 file(s) with 2 failure(s)."));
+    assert_eq!(stderr.matches("parse invoice: empty document").count(), 2);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn validate_reports_missing_fields_per_file() {
+    let dir = scratch_dir("validate");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+    let incomplete = INVOICE.replace("<cbc:IssueDate>2024-01-01</cbc:IssueDate>", "");
+    fs::write(dir.join("incomplete.xml"), incomplete).expect("write invoice");
+
+    let output = ruble(&["--validate", "--dry-run"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stderr.contains("incomplete.xml"));
+    assert!(stderr.contains("missing issue date (BT-2)"));
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Processed 1 file(s) with 1 failure(s)."));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn strict_fails_invoices_without_a_number() {
+    let dir = scratch_dir("strict");
+    let unnumbered = INVOICE.replacen("<cbc:ID>INV-1</cbc:ID>", "", 1);
+    fs::write(dir.join("invoice.xml"), unnumbered).expect("write invoice");
+
+    // Lenient by default: the gap becomes an empty field.
+    let output = ruble(&["--dry-run"], &dir);
+    assert!(output.status.success());
+
+    let output = ruble(&["--strict", "--dry-run"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stderr.contains("missing invoice number (BT-1)"));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn embedded_pdf_keeps_its_sanitized_filename() {
+    let dir = scratch_dir("embedded-name");
+    let input = dir.join("in");
+    fs::create_dir_all(&input).expect("create input dir");
+    fs::write(input.join("invoice.xml"), INVOICE).expect("write invoice");
+    let hostile = INVOICE
+        .replace(r#"filename="orig.pdf""#, r#"filename="../../etc/x.pdf""#)
+        .replace("INV-1", "INV-2");
+    fs::write(input.join("hostile.xml"), hostile).expect("write invoice");
+
+    let output = ruble(&[], &input);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(input.join("orig.pdf")).expect("read embedded"),
+        b"hello"
+    );
+    assert!(input.join("x.pdf").exists());
+    assert!(!dir.join("etc").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn writes_every_embedded_attachment() {
+    let dir = scratch_dir("attachments");
+    let xml = INVOICE.replace(
+        "  <cac:AccountingSupplierParty>",
+        r#"  <cac:AdditionalDocumentReference>
+    <cbc:ID>timesheet</cbc:ID>
+    <cac:Attachment>
+      <cbc:EmbeddedDocumentBinaryObject mimeCode="text/csv">YSxi</cbc:EmbeddedDocumentBinaryObject>
+    </cac:Attachment>
+  </cac:AdditionalDocumentReference>
+  <cac:AccountingSupplierParty>"#,
+    );
+    fs::write(dir.join("invoice.xml"), xml).expect("write invoice");
+
+    let output = ruble(&[], &dir);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read(dir.join("invoice_INV-1_embedded_1.csv")).expect("read csv"),
+        b"a,b"
+    );
+    assert_eq!(
+        fs::read(dir.join("orig.pdf")).expect("read embedded"),
+        b"hello"
+    );
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn attachment_names_without_extension_get_one_from_the_mime_type() {
+    let dir = scratch_dir("attachment-extension");
+    let xml = INVOICE
+        .replace(
+            r#"filename="orig.pdf""#,
+            r#"filename="original""#,
+        )
+        .replace(
+            "  <cac:AccountingSupplierParty>",
+            r#"  <cac:AdditionalDocumentReference>
+    <cbc:ID>timesheet</cbc:ID>
+    <cac:Attachment>
+      <cbc:EmbeddedDocumentBinaryObject mimeCode="text/csv" filename="hours">YSxi</cbc:EmbeddedDocumentBinaryObject>
+    </cac:Attachment>
+  </cac:AdditionalDocumentReference>
+  <cac:AccountingSupplierParty>"#,
+        );
+    fs::write(dir.join("invoice.xml"), xml).expect("write invoice");
+
+    let output = ruble(&[], &dir);
+    assert!(output.status.success());
+    assert_eq!(fs::read(dir.join("hours.csv")).expect("read csv"), b"a,b");
+    assert_eq!(
+        fs::read(dir.join("original.pdf")).expect("read embedded"),
+        b"hello"
+    );
+    assert!(!dir.join("hours").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn flatten_keeps_colliding_invoice_numbers_apart() {
+    let dir = scratch_dir("flatten");
+    let input = dir.join("in");
+    for month in ["01", "02"] {
+        let month_dir = input.join("2024").join(month);
+        fs::create_dir_all(&month_dir).expect("create input dir");
+        fs::write(month_dir.join("invoice.xml"), INVOICE).expect("write invoice");
+    }
+    let out_dir = dir.join("out");
+
+    let output = ruble(
+        &[
+            "--flatten",
+            "--output",
+            out_dir.to_str().expect("utf-8 path"),
+        ],
+        &input,
+    );
+    assert!(output.status.success());
+    assert!(out_dir.join("invoice_INV-1_generated.pdf").exists());
+    assert!(out_dir.join("invoice_INV-1_generated_2.pdf").exists());
+    assert!(out_dir.join("orig.pdf").exists());
+    assert!(out_dir.join("orig_2.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn output_mirrors_nested_input_tree() {
+    let dir = scratch_dir("mirror");
+    let input = dir.join("in");
+    for (month, number) in [("01", "INV-1"), ("02", "INV-2")] {
+        let month_dir = input.join("2024").join(month);
+        fs::create_dir_all(&month_dir).expect("create input dir");
+        let xml = INVOICE.replace("INV-1", number);
+        fs::write(month_dir.join("invoice.xml"), xml).expect("write invoice");
+    }
+    fs::write(input.join("top.xml"), INVOICE.replace("INV-1", "INV-0")).expect("write invoice");
+    let out_dir = dir.join("out");
+
+    let output = ruble(
+        &[
+            "--no-embedded",
+            "--output",
+            out_dir.to_str().expect("utf-8 path"),
+        ],
+        &input,
+    );
+    assert!(output.status.success());
+    assert!(out_dir.join("invoice_INV-0_generated.pdf").exists());
+    assert!(out_dir.join("2024/01/invoice_INV-1_generated.pdf").exists());
+    assert!(out_dir.join("2024/02/invoice_INV-2_generated.pdf").exists());
+    assert!(!out_dir.join("invoice_INV-1_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn max_depth_limits_directory_recursion() {
+    let dir = scratch_dir("max-depth");
+    fs::create_dir_all(dir.join("sub")).expect("create nested dir");
+    fs::write(dir.join("top.xml"), INVOICE).expect("write invoice");
+    let nested = INVOICE.replace("INV-1", "INV-2");
+    fs::write(dir.join("sub/nested.xml"), nested).expect("write nested invoice");
+
+    for args in [["--max-depth", "1"].as_slice(), &["--no-recursive"]] {
+        let output = ruble(&[args, &["--no-embedded"]].concat(), &dir);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+        assert!(stdout.contains("Processed 1 file(s)"));
+        assert!(dir.join("invoice_INV-1_generated.pdf").exists());
+        assert!(!dir.join("sub/invoice_INV-2_generated.pdf").exists());
+    }
+
+    let output = ruble(&["--no-embedded"], &dir);
+    assert!(output.status.success());
+    assert!(dir.join("sub/invoice_INV-2_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_symlinks_traverses_linked_directories() {
+    let dir = scratch_dir("symlinks");
+    let store = dir.join("store");
+    let input = dir.join("in");
+    fs::create_dir_all(&store).expect("create store dir");
+    fs::create_dir_all(&input).expect("create input dir");
+    fs::write(store.join("invoice.xml"), INVOICE).expect("write invoice");
+    std::os::unix::fs::symlink(&store, input.join("linked")).expect("link store");
+
+    let output = ruble(&["--no-embedded"], &input);
+    assert!(output.status.success());
+    assert!(!store.join("invoice_INV-1_generated.pdf").exists());
+
+    let output = ruble(&["--no-embedded", "--follow-symlinks"], &input);
+    assert!(output.status.success());
+    assert!(store.join("invoice_INV-1_generated.pdf").exists());
+
+    // A link back to an ancestor is reported per entry instead of looping.
+    std::os::unix::fs::symlink(&input, store.join("loop")).expect("link loop");
+    let output = ruble(&["--no-embedded", "--follow-symlinks"], &input);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("with 1 failure(s)"));
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stderr.contains("loop"));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn exclude_globs_skip_matching_folders_and_files() {
+    let dir = scratch_dir("exclude");
+    fs::create_dir_all(dir.join("archive/2023")).expect("create archive dir");
+    fs::create_dir_all(dir.join("current")).expect("create current dir");
+    fs::write(dir.join("archive/2023/old.xml"), INVOICE).expect("write archived invoice");
+    fs::write(dir.join("current/new.xml"), INVOICE).expect("write invoice");
+    fs::write(dir.join("current/next.draft.xml"), "<Invoice>").expect("write draft");
+
+    let output = ruble(
+        &[
+            "--no-embedded",
+            "--exclude",
+            "archive",
+            "--exclude",
+            "*.draft.xml",
+        ],
+        &dir,
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Processed 1 file(s) with 0 failure(s)."));
+    assert!(dir.join("current/invoice_INV-1_generated.pdf").exists());
+    assert!(
+        !dir.join("archive/2023/invoice_INV-1_generated.pdf")
+            .exists()
+    );
+
+    let output = ruble(&["--exclude", "[oops"], &dir);
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn json_logs_carry_per_file_fields() {
+    let dir = scratch_dir("log-json");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+
+    let output = ruble(&["--log-format", "json", "--dry-run"], &dir);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    let closed = stderr
+        .lines()
+        .find(|line| line.contains(r#""outcome":"ok""#))
+        .expect("span close line");
+    assert!(closed.contains(r#""invoice_number":"INV-1""#));
+    assert!(closed.contains("invoice.xml"));
+
+    let quiet = ruble(&["--log-level", "off", "--dry-run"], &dir);
+    assert!(quiet.stderr.is_empty());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn gzipped_invoices_are_decompressed() {
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    let dir = scratch_dir("gzip");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(INVOICE.as_bytes()).expect("compress");
+    fs::write(
+        dir.join("invoice.xml.gz"),
+        encoder.finish().expect("compress"),
+    )
+    .expect("write invoice");
+
+    let output = ruble(&["--json"], &dir);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains(r#""invoice_number": "INV-1""#));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn oversized_decompression_fails_cleanly() {
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+
+    let dir = scratch_dir("gzip-bomb");
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(INVOICE.as_bytes()).expect("compress");
+    fs::write(
+        dir.join("invoice.xml.gz"),
+        encoder.finish().expect("compress"),
+    )
+    .expect("write invoice");
+
+    let output = ruble(&["--json", "--max-decompressed-bytes", "100"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stderr.contains("decompresses to more than 100 bytes"));
+    assert!(output.stdout.is_empty());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn zip_archive_entries_are_converted() {
+    use zip::write::SimpleFileOptions;
+
+    let dir = scratch_dir("zip");
+    let archive_path = dir.join("batch.zip");
+    let mut archive = zip::ZipWriter::new(fs::File::create(&archive_path).expect("create zip"));
+    let options = SimpleFileOptions::default();
+    archive
+        .start_file("2024/01/invoice.xml", options)
+        .expect("start entry");
+    archive.write_all(INVOICE.as_bytes()).expect("write entry");
+    archive
+        .start_file("2024/02/broken.xml", options)
+        .expect("start entry");
+    archive.write_all(b"<Invoice>").expect("write entry");
+    archive
+        .start_file("readme.txt", options)
+        .expect("start entry");
+    archive.write_all(b"not an invoice").expect("write entry");
+    archive.finish().expect("finish zip");
+    let out_dir = dir.join("out");
+
+    let output = ruble(
+        &["--output", out_dir.to_str().expect("utf-8 path")],
+        &archive_path,
+    );
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stdout.contains("Processed 1 file(s) with 1 failure(s)."));
+    assert!(stderr.contains("batch.zip!/2024/02/broken.xml"));
+    assert!(out_dir.join("2024/01/invoice_INV-1_generated.pdf").exists());
+    assert!(out_dir.join("2024/01/orig.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn latin1_invoice_keeps_accented_names() {
+    let dir = scratch_dir("latin1");
+    let xml = INVOICE
+        .replace(r#"encoding="UTF-8""#, r#"encoding="ISO-8859-1""#)
+        .replace("Supplier Inc", "Café SARL");
+    let latin1: Vec<u8> = xml.chars().map(|ch| ch as u8).collect();
+    fs::write(dir.join("invoice.xml"), latin1).expect("write invoice");
+
+    let output = ruble(&["--json"], &dir);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains(r#""supplier_name": "Café SARL""#));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn utf16_invoices_with_a_bom_are_transcoded() {
+    let dir = scratch_dir("utf16");
+    let xml = INVOICE.replace(r#"encoding="UTF-8""#, r#"encoding="UTF-16""#);
+    let units = || xml.encode_utf16();
+    let little: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain(units().flat_map(u16::to_le_bytes))
+        .collect();
+    let big: Vec<u8> = [0xFE, 0xFF]
+        .into_iter()
+        .chain(units().flat_map(u16::to_be_bytes))
+        .collect();
+    fs::write(dir.join("little.xml"), little).expect("write invoice");
+    fs::write(dir.join("big.xml"), big).expect("write invoice");
+
+    let output = ruble(&["--json"], &dir);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert_eq!(stdout.matches(r#""invoice_number": "INV-1""#).count(), 2);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn html_format_writes_html_pages() {
+    let dir = scratch_dir("html");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+
+    let output = ruble(&["--format", "html", "--no-embedded"], &dir);
+    assert!(output.status.success());
+    let html = fs::read_to_string(dir.join("invoice_INV-1_generated.html")).expect("read html");
+    assert!(html.contains("Invoice Number: INV-1"));
+    assert!(!dir.join("invoice_INV-1_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn skip_existing_only_regenerates_changed_sources() {
+    let dir = scratch_dir("skip-existing");
+    let source = dir.join("invoice.xml");
+    fs::write(&source, INVOICE).expect("write invoice");
+
+    let args = ["--skip-existing", "--no-embedded"];
+    let first = ruble(&args, &dir);
+    assert!(first.status.success());
+    let stdout = String::from_utf8(first.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Skipped 0 up-to-date file(s)."));
+
+    let second = ruble(&args, &dir);
+    let stdout = String::from_utf8(second.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Skipped 1 up-to-date file(s)."));
+
+    // Rewriting the source makes it newer than the PDF again.
+    std::thread::sleep(Duration::from_millis(20));
+    fs::write(&source, INVOICE).expect("rewrite invoice");
+    let third = ruble(&args, &dir);
+    let stdout = String::from_utf8(third.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Skipped 0 up-to-date file(s)."));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn overwrite_never_keeps_existing_outputs() {
+    let dir = scratch_dir("overwrite-never");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+    let generated = dir.join("invoice_INV-1_generated.pdf");
+    fs::write(&generated, "old pdf").expect("write existing pdf");
+
+    // The missing attachment is still extracted; the existing PDF is kept.
+    let first = ruble(&["--overwrite", "never"], &dir);
+    assert!(first.status.success());
+    let stdout = String::from_utf8(first.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Skipped 0 file(s) with existing outputs."));
+    assert_eq!(fs::read(&generated).expect("read pdf"), b"old pdf");
+    assert_eq!(
+        fs::read(dir.join("orig.pdf")).expect("read attachment"),
+        b"hello"
+    );
+
+    let second = ruble(&["--overwrite", "never"], &dir);
+    let stdout = String::from_utf8(second.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Skipped 1 file(s) with existing outputs."));
+    assert_eq!(fs::read(&generated).expect("read pdf"), b"old pdf");
+
+    let third = ruble(&["--overwrite", "always"], &dir);
+    let stdout = String::from_utf8(third.stdout).expect("utf-8 stdout");
+    assert!(!stdout.contains("Skipped"));
+    assert!(
+        fs::read(&generated)
+            .expect("read pdf")
+            .starts_with(b"%PDF-")
+    );
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn csv_lists_every_line_item_in_input_order() {
+    let dir = scratch_dir("csv");
+    let input = dir.join("in");
+    fs::create_dir_all(&input).expect("create input dir");
+    for n in 1..=3 {
+        let xml = INVOICE.replace("INV-1", &format!("INV-{n}"));
+        fs::write(input.join(format!("invoice-{n}.xml")), xml).expect("write invoice");
+    }
+    let csv_path = dir.join("lines.csv");
+
+    let output = ruble(
+        &[
+            "--jobs",
+            "3",
+            "--csv",
+            csv_path.to_str().expect("utf-8 path"),
+        ],
+        &input,
+    );
+    assert!(output.status.success());
+    let csv = fs::read_to_string(&csv_path).expect("read csv");
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        rows[0],
+        "invoice_number,issue_date,supplier,description,quantity,unit_price,total,currency,order_line_id,seller_item_id,buyer_item_id,preceding_invoice"
+    );
+    assert_eq!(rows.len(), 4);
+    for (n, row) in rows[1..].iter().enumerate() {
+        assert_eq!(
+            *row,
+            format!(
+                "INV-{},2024-01-01,Supplier Inc,Widget,1,10.00,10.00,EUR,,,,",
+                n + 1
+            )
+        );
+    }
+    assert!(dir.join("in/invoice_INV-1_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn ndjson_streams_one_object_per_converted_invoice() {
+    let dir = scratch_dir("ndjson");
+    let input = dir.join("in");
+    fs::create_dir_all(&input).expect("create input dir");
+    for n in 1..=5 {
+        let xml = INVOICE.replace("INV-1", &format!("INV-{n}"));
+        fs::write(input.join(format!("invoice-{n}.xml")), xml).expect("write invoice");
+    }
+    fs::write(input.join("invoice-6.xml"), "<Invoice>").expect("write broken invoice");
+    let ndjson_path = dir.join("invoices.ndjson");
+
+    let output = ruble(
+        &[
+            "--jobs",
+            "3",
+            "--no-embedded",
+            "--ndjson",
+            ndjson_path.to_str().expect("utf-8 path"),
+        ],
+        &input,
+    );
+    assert!(!output.status.success());
+    let ndjson = fs::read_to_string(&ndjson_path).expect("read ndjson");
+    let lines: Vec<&str> = ndjson.lines().collect();
+    assert_eq!(lines.len(), 5);
+    for (n, line) in lines.iter().enumerate() {
+        assert!(line.starts_with('{') && line.ends_with('}'));
+        assert!(line.contains(&format!(r#""invoice_number":"INV-{}""#, n + 1)));
+    }
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn report_lists_one_record_per_input_file() {
+    let dir = scratch_dir("report");
+    let input = dir.join("in");
+    fs::create_dir_all(&input).expect("create input dir");
+    fs::write(input.join("a.xml"), INVOICE).expect("write invoice");
+    fs::write(input.join("b.xml"), "<Invoice>").expect("write broken invoice");
+    let report_path = dir.join("report.json");
+
+    let output = ruble(
+        &[
+            "--no-embedded",
+            "--report",
+            report_path.to_str().expect("utf-8 path"),
+        ],
+        &input,
+    );
+    assert!(!output.status.success());
+    let report = fs::read_to_string(&report_path).expect("read report");
+    assert!(report.contains(r#""processed": 1"#));
+    assert!(report.contains(r#""failures": 1"#));
+    assert_eq!(report.matches(r#""source":"#).count(), 2);
+    assert!(report.contains(r#""invoice_number": "INV-1""#));
+    assert!(report.contains(r#""status": "ok""#));
+    assert!(report.contains("invoice_INV-1_generated.pdf"));
+    assert!(report.contains(r#""status": "failed""#));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn watch_converts_invoices_added_later() {
+    let dir = scratch_dir("watch");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ruble"))
+        .args(["--watch", "--no-embedded"])
+        .arg(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run ruble");
+    // Wait until the watcher is registered before adding the file.
+    let mut stderr = BufReader::new(child.stderr.take().expect("stderr"));
+    let mut line = String::new();
+    while !line.contains("watching for new invoices") {
+        line.clear();
+        let read = stderr.read_line(&mut line).expect("read stderr");
+        assert!(read > 0, "ruble exited before watching");
+    }
+
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+    let pdf = dir.join("invoice_INV-1_generated.pdf");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !pdf.exists() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    let still_running = child.try_wait().expect("poll ruble").is_none();
+    child.kill().ok();
+    child.wait().ok();
+    assert!(pdf.exists());
+    assert!(still_running);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn unwritable_output_fails_before_processing() {
+    let dir = scratch_dir("unwritable");
+    let input = dir.join("in");
+    fs::create_dir_all(&input).expect("create input dir");
+    fs::write(input.join("invoice.xml"), INVOICE).expect("write invoice");
+    // A directory cannot be created below a regular file, whatever the user.
+    let blocker = dir.join("blocker");
+    fs::write(&blocker, "").expect("write blocker");
+    let out = blocker.join("out");
+
+    let output = ruble(&["--output", out.to_str().expect("utf-8 path")], &input);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("utf-8 stderr");
+    assert!(stderr.contains("is not writable"));
+    assert!(!stderr.contains("invoice.xml"));
+    assert!(output.stdout.is_empty());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn files_from_converts_only_the_listed_files() {
+    let dir = scratch_dir("files-from");
+    for n in 1..=3 {
+        let xml = INVOICE.replace("INV-1", &format!("INV-{n}"));
+        fs::write(dir.join(format!("invoice-{n}.xml")), xml).expect("write invoice");
+    }
+    let list = dir.join("files.txt");
+    let listed = [dir.join("invoice-1.xml"), dir.join("invoice-3.xml")];
+    let listed: Vec<&str> = listed
+        .iter()
+        .map(|path| path.to_str().expect("utf-8 path"))
+        .collect();
+    fs::write(&list, format!("{}\n\n{}\n", listed[0], listed[1])).expect("write list");
+
+    let output = ruble(&["--files-from", list.to_str().expect("utf-8 path")], &dir);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Processed 2 file(s)"));
+    assert!(dir.join("invoice_INV-1_generated.pdf").exists());
+    assert!(!dir.join("invoice_INV-2_generated.pdf").exists());
+    assert!(dir.join("invoice_INV-3_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn files_from_mirrors_absolute_and_relative_entries() {
+    let dir = scratch_dir("files-from-mirror");
+    let nested = dir.join("in/2024/01");
+    fs::create_dir_all(&nested).expect("create input tree");
+    fs::write(nested.join("a.xml"), INVOICE).expect("write invoice");
+    let list = dir.join("files.txt");
+    let run = |entry: &str, args: &[&str]| {
+        fs::write(&list, format!("{entry}\n")).expect("write list");
+        Command::new(env!("CARGO_BIN_EXE_ruble"))
+            .args(["--files-from", "files.txt", "--output", "out"])
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .expect("run ruble")
+    };
+
+    // An absolute entry mirrors relative to a relative input argument.
+    let absolute = nested.join("a.xml");
+    let output = run(absolute.to_str().expect("utf-8 path"), &["in"]);
+    assert!(output.status.success());
+    assert!(dir.join("out/2024/01/invoice_INV-1_generated.pdf").exists());
+    assert!(!dir.join("out/invoice_INV-1_generated.pdf").exists());
+
+    // Without an input argument the current directory is the root.
+    let output = run("in/2024/01/a.xml", &[]);
+    assert!(output.status.success());
+    assert!(
+        dir.join("out/in/2024/01/invoice_INV-1_generated.pdf")
+            .exists()
+    );
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn files_from_accepts_crlf_lists() {
+    let dir = scratch_dir("files-from-crlf");
+    fs::write(dir.join("invoice.xml"), INVOICE).expect("write invoice");
+    let list = dir.join("files.txt");
+    let listed = dir.join("invoice.xml");
+    let listed = listed.to_str().expect("utf-8 path");
+    fs::write(&list, format!("{listed}  \r\n\r\n")).expect("write list");
+
+    let output = ruble(&["--files-from", list.to_str().expect("utf-8 path")], &dir);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("Processed 1 file(s) with 0 failure(s)"));
+    assert!(dir.join("invoice_INV-1_generated.pdf").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn subcommands_route_to_convert_extract_and_inspect() {
+    let dir = scratch_dir("subcommands");
+    let write_invoice = |name: &str| {
+        let input = dir.join(name);
+        fs::create_dir_all(&input).expect("create input dir");
+        fs::write(input.join("invoice.xml"), INVOICE).expect("write invoice");
+        input
+    };
+
+    let input = write_invoice("convert");
+    let output = ruble(&["convert"], &input);
+    assert!(output.status.success());
+    assert!(input.join("invoice_INV-1_generated.pdf").exists());
+    assert!(input.join("orig.pdf").exists());
+
+    let input = write_invoice("extract");
+    let output = ruble(&["extract"], &input);
+    assert!(output.status.success());
+    assert!(!input.join("invoice_INV-1_generated.pdf").exists());
+    assert_eq!(
+        fs::read(input.join("orig.pdf")).expect("read embedded"),
+        b"hello"
+    );
+
+    let input = write_invoice("inspect");
+    let output = ruble(&["inspect"], &input);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("utf-8 stdout");
+    assert!(stdout.contains("\"invoice_number\": \"INV-1\""));
+    let files: Vec<_> = fs::read_dir(&input).expect("list input").collect();
+    assert_eq!(files.len(), 1);
+
+    fs::remove_dir_all(&dir).ok();
+}